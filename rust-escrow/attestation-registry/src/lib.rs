@@ -0,0 +1,110 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupSet;
+use near_sdk::{env, log, near_bindgen, AccountId};
+
+/// Minimal attestation registry, stands in for a KYC provider queried by the Conditional Escrow
+/// contracts through is_verified
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct AttestationRegistry {
+    owner_id: AccountId,
+    verified_accounts: LookupSet<AccountId>,
+}
+
+impl Default for AttestationRegistry {
+    fn default() -> Self {
+        env::panic_str("AttestationRegistry should be initialized before usage")
+    }
+}
+
+#[near_bindgen]
+impl AttestationRegistry {
+    #[init]
+    pub fn new(owner_id: AccountId) -> Self {
+        if env::state_exists() {
+            env::panic_str("ERR_ALREADY_INITIALIZED");
+        }
+
+        Self {
+            owner_id,
+            verified_accounts: LookupSet::new(b"v".to_vec()),
+        }
+    }
+
+    pub fn get_owner_id(&self) -> AccountId {
+        self.owner_id.clone()
+    }
+
+    pub fn is_verified(&self, account_id: AccountId) -> bool {
+        self.verified_accounts.contains(&account_id)
+    }
+
+    pub fn attest(&mut self, account_id: AccountId) {
+        self.assert_owner();
+
+        self.verified_accounts.insert(&account_id);
+
+        log!("{} verified", &account_id);
+    }
+
+    pub fn revoke(&mut self, account_id: AccountId) {
+        self.assert_owner();
+
+        self.verified_accounts.remove(&account_id);
+
+        log!("{} verification revoked", &account_id);
+    }
+
+    fn assert_owner(&self) {
+        if env::predecessor_account_id() != self.owner_id {
+            env::panic_str("ERR_NOT_OWNER");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::test_env::{alice, bob, carol};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn setup_contract() -> (VMContextBuilder, AttestationRegistry) {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context.predecessor_account_id(alice()).build());
+        let contract = AttestationRegistry::new(alice());
+        (context, contract)
+    }
+
+    #[test]
+    fn test_attest() {
+        let (_context, mut contract) = setup_contract();
+
+        assert!(!contract.is_verified(bob()));
+
+        contract.attest(bob());
+
+        assert!(contract.is_verified(bob()));
+        assert!(!contract.is_verified(carol()));
+    }
+
+    #[test]
+    fn test_revoke() {
+        let (_context, mut contract) = setup_contract();
+
+        contract.attest(bob());
+        contract.revoke(bob());
+
+        assert!(!contract.is_verified(bob()));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn test_attest_not_owner() {
+        let (mut context, mut contract) = setup_contract();
+
+        testing_env!(context.predecessor_account_id(bob()).build());
+
+        contract.attest(bob());
+    }
+}
@@ -33,6 +33,11 @@ sh build.sh
 cargo test -- --nocapture --exact
 cd $ROOT
 
+cd attestation-registry
+sh build.sh
+cargo test -- --nocapture --exact
+cd $ROOT
+
 cargo test -- --nocapture --exact
 RUSTFLAGS='-C link-arg=-s' cargo build --target wasm32-unknown-unknown --release
 cp ./target/wasm32-unknown-unknown/release/escrow_factory.wasm src/
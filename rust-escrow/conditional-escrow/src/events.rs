@@ -0,0 +1,226 @@
+use near_sdk::json_types::U128;
+use near_sdk::serde::Serialize;
+use near_sdk::serde_json;
+use near_sdk::{env, AccountId};
+
+use crate::{CampaignStatus, ReleaseCondition};
+
+// NEP-297 standard name and version of the events emitted by this contract
+pub const EVENT_STANDARD: &str = "nearholdings";
+pub const EVENT_STANDARD_VERSION: &str = "1.0.0";
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DepositEvent {
+    pub account_id: AccountId,
+    pub amount: U128,
+    pub balance: U128,
+    pub total_funds: U128,
+    pub unpaid_funding_amount: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WithdrawEvent {
+    pub account_id: AccountId,
+    pub amount: U128,
+    pub balance: U128,
+    pub total_funds: U128,
+    pub unpaid_funding_amount: U128,
+}
+
+/// Transfer of a withdrawal failed, the balance was restored
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WithdrawFailedEvent {
+    pub account_id: AccountId,
+    pub amount: U128,
+    pub balance: U128,
+    pub total_funds: U128,
+    pub unpaid_funding_amount: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PositionTransferRequestedEvent {
+    pub sender_id: AccountId,
+    pub receiver_id: AccountId,
+    pub amount: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PositionTransferredEvent {
+    pub sender_id: AccountId,
+    pub receiver_id: AccountId,
+    pub amount: U128,
+    pub sender_balance: U128,
+    pub receiver_balance: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DeadlineExtensionProposedEvent {
+    pub expires_at: u64,
+    pub current_expires_at: u64,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DeadlineExtensionVotedEvent {
+    pub account_id: AccountId,
+    pub approve: bool,
+    pub weight: U128,
+    pub votes_for: U128,
+    pub votes_against: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DeadlineExtendedEvent {
+    pub previous_expires_at: u64,
+    pub expires_at: u64,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ReleaseConditionMetEvent {
+    pub index: u32,
+    pub condition: ReleaseCondition,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DelegateStartedEvent {
+    pub dao_name: String,
+    pub dao_amount: U128,
+    pub ft_amount: U128,
+    pub fee_amount: U128,
+    pub depositors_count: u64,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DelegateSucceededEvent {
+    pub dao_name: String,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DelegateFailedEvent {
+    pub dao_name: String,
+    pub is_dao_created: bool,
+    pub is_ft_created: bool,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MilestoneVotedEvent {
+    pub milestone: u32,
+    pub account_id: AccountId,
+    pub approve: bool,
+    pub weight: U128,
+    pub votes_for: U128,
+    pub votes_against: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MilestoneApprovedEvent {
+    pub milestone: u32,
+    pub amount: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MilestoneRejectedEvent {
+    pub milestone: u32,
+    pub undisbursed_funds: U128,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StatusChangedEvent {
+    pub previous_status: CampaignStatus,
+    pub status: CampaignStatus,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MatchFundedEvent {
+    pub sponsor_account_id: AccountId,
+    pub amount: U128,
+    pub match_pool: U128,
+}
+
+/// Part of a deposit matched from the pool of the sponsor
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DepositMatchedEvent {
+    pub sponsor_account_id: AccountId,
+    pub account_id: AccountId,
+    pub amount: U128,
+    pub match_pool: U128,
+    pub total_funds: U128,
+}
+
+/// The unused pool, plus the matched funds if the depositors are refunded
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MatchWithdrawnEvent {
+    pub sponsor_account_id: AccountId,
+    pub amount: U128,
+    pub matched_funds: U128,
+    pub total_funds: U128,
+}
+
+/// Events of the Conditional Escrow lifecycle, logged as `EVENT_JSON:{...}`
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+pub enum EscrowEvent {
+    Deposit(DepositEvent),
+    Withdraw(WithdrawEvent),
+    WithdrawFailed(WithdrawFailedEvent),
+    PositionTransferRequested(PositionTransferRequestedEvent),
+    PositionTransferred(PositionTransferredEvent),
+    DeadlineExtensionProposed(DeadlineExtensionProposedEvent),
+    DeadlineExtensionVoted(DeadlineExtensionVotedEvent),
+    DeadlineExtended(DeadlineExtendedEvent),
+    ReleaseConditionMet(ReleaseConditionMetEvent),
+    DelegateStarted(DelegateStartedEvent),
+    DelegateSucceeded(DelegateSucceededEvent),
+    DelegateFailed(DelegateFailedEvent),
+    MilestoneVoted(MilestoneVotedEvent),
+    MilestoneApproved(MilestoneApprovedEvent),
+    MilestoneRejected(MilestoneRejectedEvent),
+    StatusChanged(StatusChangedEvent),
+    MatchFunded(MatchFundedEvent),
+    DepositMatched(DepositMatchedEvent),
+    MatchWithdrawn(MatchWithdrawnEvent),
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct EventLog<'a> {
+    standard: &'static str,
+    version: &'static str,
+    #[serde(flatten)]
+    event: &'a EscrowEvent,
+}
+
+impl EscrowEvent {
+    pub fn to_json_string(&self) -> String {
+        serde_json::to_string(&EventLog {
+            standard: EVENT_STANDARD,
+            version: EVENT_STANDARD_VERSION,
+            event: self,
+        })
+        .unwrap()
+    }
+
+    pub fn emit(&self) {
+        env::log_str(&format!("EVENT_JSON:{}", self.to_json_string()));
+    }
+}
@@ -1,32 +1,346 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::UnorderedMap;
+use near_sdk::collections::{LookupMap, LookupSet, TreeMap, UnorderedMap};
 use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::serde_json::json;
 use near_sdk::{env, log, near_bindgen, Gas};
-use near_sdk::{AccountId, Balance, Promise, PromiseResult};
+use near_sdk::{AccountId, Balance, Promise, PromiseOrValue, PromiseResult, StorageUsage};
+
+pub mod events;
+use events::*;
+
+// Lints triggered by the code that construct_uint generates
+#[allow(clippy::assign_op_pattern, clippy::manual_div_ceil)]
+mod math {
+    uint::construct_uint! {
+        /// 256-bit unsigned integer for intermediate pro-rata calculations
+        pub struct U256(4);
+    }
+}
+use crate::math::U256;
 
 /// Amount of gas
 pub const GAS_FOR_CREATE_DAO: Gas = Gas(150_000_000_000_000);
 pub const GAS_FOR_CREATE_FT: Gas = Gas(50_000_000_000_000);
 pub const GAS_FOR_PROPOSAL: Gas = Gas(25_000_000_000_000);
 pub const GAS_FOR_CALLBACK: Gas = Gas(2_000_000_000_000);
+pub const GAS_FOR_DELEGATE_CALLBACK: Gas = Gas(10_000_000_000_000);
+pub const GAS_FOR_FT_TRANSFER: Gas = Gas(10_000_000_000_000);
+pub const GAS_FOR_FT_TRANSFER_CALLBACK: Gas = Gas(5_000_000_000_000);
+pub const GAS_FOR_WITHDRAW_CALLBACK: Gas = Gas(5_000_000_000_000);
+pub const GAS_FOR_PAYOUT_CALLBACK: Gas = Gas(3_000_000_000_000);
+pub const GAS_FOR_GET_DAO_ACCOUNT: Gas = Gas(5_000_000_000_000);
+pub const GAS_FOR_FORWARD_FUNDS: Gas = Gas(30_000_000_000_000);
+pub const GAS_FOR_RELEASE_CONDITION: Gas = Gas(10_000_000_000_000);
+pub const GAS_FOR_VERIFY_ACCOUNT: Gas = Gas(5_000_000_000_000);
+pub const GAS_FOR_VERIFY_CALLBACK: Gas = Gas(15_000_000_000_000);
 
 // Attached deposits
 pub const FT_ATTACHED_DEPOSIT: Balance = 5_000_000_000_000_000_000_000_000; // 5 Near
+pub const ONE_YOCTO: Balance = 1;
+
+// Fees
+pub const MAX_FEE_BASIS_POINTS: u16 = 10_000; // 100%
+
+// Time to retry an unsuccessful delegation before depositors may withdraw
+pub const DELEGATION_RETRY_PERIOD: u64 = 604_800_000_000_000; // 7 days
+
+// Time after which a delegation whose on_delegate_callback never completed is deemed unsuccessful
+pub const DELEGATION_CALLBACK_TIMEOUT: u64 = 3_600_000_000_000; // 1 hour
+
+// Time to call delegate_funds after expires_at before depositors may withdraw
+pub const DEFAULT_DELEGATION_WINDOW: u64 = 2_592_000_000_000_000; // 30 days
+
+// Reward of the caller of a successful delegate_funds
+pub const MAX_KEEPER_REWARD_BASIS_POINTS: u16 = 100; // 1%
+
+// Penalty of the withdrawals during funding once the cooling-off period ended
+pub const MAX_EARLY_EXIT_PENALTY_BASIS_POINTS: u16 = 10_000; // 100%
+
+// Deadline extension votes
+pub const MAX_EXTENSION_BASIS_POINTS: u16 = 10_000; // 100%
+pub const DEFAULT_EXTENSION_THRESHOLD_BASIS_POINTS: u16 = 5_000; // Simple majority
+
+// Extra share weight of the deposits made early, decaying to 0 over the bonus period
+pub const MAX_EARLY_BONUS_BASIS_POINTS: u16 = 10_000; // 100%
+pub const DEFAULT_EARLY_BONUS_PERIOD: u64 = 86_400_000_000_000; // 24 hours
+
+// Milestone tranches, the basis points of all the tranches add up to the max
+pub const MAX_TRANCHE_BASIS_POINTS: u16 = 10_000; // 100%
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum CampaignStatus {
+    /// Accepting deposits until expires_at or until the funding_amount_limit (hard cap) is reached,
+    /// oversubscribed campaigns accept deposits until expires_at
+    Funding,
+    /// Hard cap reached, or soft cap reached by expires_at, waiting for delegate_funds
+    Funded,
+    /// expires_at passed without reaching the soft cap, the release conditions were not met by the
+    /// release_conditions_deadline, or delegate_funds was not called within the delegation_window.
+    /// Depositors may withdraw
+    Expired,
+    /// delegate_funds called, waiting for on_delegate_callback. Deemed unsuccessful if the callback
+    /// did not complete within the DELEGATION_CALLBACK_TIMEOUT
+    Delegating,
+    /// DAO and FT created, funds transferred to the DAO
+    Delegated,
+    /// on_delegate_callback reported an unsuccessful DAO or FT creation, delegate_funds may be
+    /// retried. Depositors may withdraw after the DELEGATION_RETRY_PERIOD if the DAO was not created
+    DelegationFailed,
+    /// Campaign cancelled by the creator before the delegation, depositors may withdraw
+    Cancelled,
+    /// A milestone was rejected, depositors may withdraw their share of the undisbursed tranches
+    MilestoneRejected,
+}
+
+impl CampaignStatus {
+    pub fn can_transition_to(&self, status: CampaignStatus) -> bool {
+        use CampaignStatus::*;
+
+        matches!(
+            (self, status),
+            (Funding, Funded)
+                | (Funding, Expired)
+                | (Funding, Cancelled)
+                | (Funded, Funding)
+                | (Funded, Expired)
+                | (Funded, Delegating)
+                | (Funded, Cancelled)
+                | (Delegating, Delegated)
+                | (Delegating, DelegationFailed)
+                | (DelegationFailed, Delegating)
+                | (DelegationFailed, Cancelled)
+                | (Delegated, MilestoneRejected)
+        )
+    }
+}
+
+/// Condition that must be met, besides the funding, before delegate_funds
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ReleaseCondition {
+    /// account_id calls attest, eg. an appraiser
+    Attestation { account_id: AccountId },
+    /// The view method_name of contract_id returns true when called with the JSON args
+    Oracle {
+        contract_id: AccountId,
+        method_name: String,
+        args: String,
+    },
+}
+
+/// Optional init arguments of a campaign, missing ones take their default
+#[derive(Serialize, Deserialize, Default)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(default)]
+pub struct CampaignConfig {
+    /// NEP-141 token deposited instead of NEAR
+    pub funding_token_id: Option<AccountId>,
+    /// Platform fee charged on successful delegation, set by the Escrow Factory
+    pub fee_basis_points: Option<u16>,
+    pub fee_account_id: Option<AccountId>,
+    /// Minimum raised by expires_at for the campaign to proceed, defaults to the hard cap
+    pub soft_cap: Option<U128>,
+    pub is_oversubscription_allowed: Option<bool>,
+    pub min_deposit: Option<U128>,
+    pub max_deposit_per_account: Option<U128>,
+    /// Defaults to the signer
+    pub creator_account_id: Option<AccountId>,
+    pub is_position_transfer_approval_required: Option<bool>,
+    pub extension_quorum_basis_points: Option<u16>,
+    pub extension_threshold_basis_points: Option<u16>,
+    pub tranche_basis_points: Option<Vec<u16>>,
+    pub milestone_verifier_account_id: Option<AccountId>,
+    pub release_conditions: Option<Vec<ReleaseCondition>>,
+    /// Funded campaigns expire if the release conditions are not met by then
+    pub release_conditions_deadline: Option<u64>,
+    /// Time to call delegate_funds after expires_at, defaults to 30 days
+    pub delegation_window: Option<u64>,
+    pub keeper_reward_basis_points: Option<u16>,
+    /// Deposits may be withdrawn early without penalty during this period
+    pub cooling_off_period: Option<u64>,
+    pub early_exit_penalty_basis_points: Option<u16>,
+    pub is_early_exit_penalty_to_creator: Option<bool>,
+    pub is_allowlist_enabled: Option<bool>,
+    /// Deposits are recorded once the registry verifies the beneficiary
+    pub attestation_registry_id: Option<AccountId>,
+    /// New depositors pay for their storage, only when funding in NEAR
+    pub is_storage_fee_charged: Option<bool>,
+    pub early_bonus_basis_points: Option<u16>,
+    /// Defaults to 24 hours
+    pub early_bonus_period: Option<u64>,
+    /// Matches the deposits 1:1 from its match pool, only when funding in NEAR
+    pub sponsor_account_id: Option<AccountId>,
+    pub is_match_in_shares: Option<bool>,
+}
+
+/// Exact share of a depositor, numerator / denominator
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ShareFraction {
+    pub numerator: U128,
+    pub denominator: U128,
+}
+
+/// Deadline extension proposed by the creator and the deposits voting for and against it
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DeadlineExtension {
+    pub expires_at: u64,
+    pub votes_for: U128,
+    pub votes_against: U128,
+}
+
+/// Aggregated view of the campaign, see get_campaign_summary
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CampaignSummary {
+    pub status: CampaignStatus,
+    pub total_funds: U128,
+    pub raised_funds: U128,
+    pub funding_amount_limit: U128,
+    pub soft_cap: U128,
+    pub unpaid_funding_amount: U128,
+    pub depositors_count: u64,
+    pub largest_depositor: Option<AccountId>,
+    pub largest_deposit: U128,
+    pub expires_at: u64,
+    // Nanoseconds until expires_at
+    pub time_remaining: u64,
+}
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct ConditionalEscrow {
     deposits: UnorderedMap<AccountId, Balance>,
+    // Non-zero deposits ordered by balance, the last one is the largest deposit
+    largest_deposits: TreeMap<(Balance, AccountId), ()>,
     expires_at: u64,
     total_funds: Balance,
+    // Hard cap, deposits stop once reached
     funding_amount_limit: u128,
+    // Minimum to proceed at expires_at
+    soft_cap: u128,
     unpaid_funding_amount: u128,
+    // Sum of the deposits, may exceed the funding_amount_limit if oversubscribed
+    raised_funds: Balance,
+    // Deposits keep arriving until expires_at and are allocated pro-rata to the funding_amount_limit
+    is_oversubscription_allowed: bool,
+    // Excess over the allocation already withdrawn by each depositor
+    excess_withdrawals: LookupMap<AccountId, Balance>,
+    // Raised funds left out by the rounded down excess of each depositor, they join the DAO funds
+    excess_dust: Balance,
+    // Bounds of the balance of each depositor
+    min_deposit: Balance,
+    max_deposit_per_account: Option<Balance>,
+    // Account that created the campaign
+    creator_account_id: AccountId,
+    // Position transfers wait for the approval of the creator
+    is_position_transfer_approval_required: bool,
+    // Position transfer requested by each sender, receiver and amount
+    pending_position_transfers: LookupMap<AccountId, (AccountId, Balance)>,
+    // Index of the next depositor to refund by refund_batch
+    refund_cursor: u64,
+    // Share of the raised funds that must vote on a deadline extension, votes disabled if None
+    extension_quorum_basis_points: Option<u16>,
+    // Share of the votes in favour that must be exceeded to extend the deadline
+    extension_threshold_basis_points: u16,
+    deadline_extension: Option<DeadlineExtension>,
+    // Proposals so far, votes of previous proposals are ignored
+    deadline_extensions_count: u32,
+    // Proposal and vote of each depositor
+    extension_votes: LookupMap<AccountId, (u32, bool)>,
+    // Share of the DAO funds released by each tranche, the full funds at once if empty
+    tranche_basis_points: Vec<u16>,
+    // Approves or rejects the milestones, depositors vote on them if None
+    milestone_verifier_account_id: Option<AccountId>,
+    // The first tranche is released on delegation, the next ones on each approved milestone
+    released_tranches: u32,
+    // DAO funds held by the escrow until their milestone is approved
+    undisbursed_funds: Balance,
+    // Milestone and vote of each depositor
+    milestone_votes: LookupMap<AccountId, (u32, bool)>,
+    milestone_votes_for: Balance,
+    milestone_votes_against: Balance,
+    // Undisbursed funds refunded to each depositor after a rejected milestone
+    tranche_refunds: LookupMap<AccountId, Balance>,
+    // Allocations refunded so far and the sum of their refunds, the last refund takes the dust
+    tranche_refunded_allocations: Balance,
+    tranche_refunded_funds: Balance,
+    release_conditions: Vec<ReleaseCondition>,
+    // Whether each release condition was met
+    met_release_conditions: Vec<bool>,
+    // Refunds open if the release conditions are not met by then
+    release_conditions_deadline: u64,
+    // Time to call delegate_funds after expires_at, or after the release_conditions_deadline
+    delegation_window: u64,
+    keeper_reward_basis_points: u16,
+    // Reserved like the fee and paid to the keeper once the delegation succeeds
+    keeper_reward: Balance,
+    // Caller of the last delegate_funds
+    keeper_account_id: Option<AccountId>,
+    // NEAR attached to create_dao by the keeper of a token funded campaign
+    keeper_deposit: Balance,
+    // Creation deposits of the failed delegations refunded by the factories to each keeper
+    keeper_refunds: LookupMap<AccountId, Balance>,
+    // Time after each deposit to withdraw in full during funding
+    cooling_off_period: u64,
+    // End of the cooling-off period of each deposit of a depositor and the amount it covers
+    cooling_off_deposits: LookupMap<AccountId, Vec<(u64, Balance)>>,
+    // Withdrawals during funding after the cooling-off period are disabled if None
+    early_exit_penalty_basis_points: Option<u16>,
+    // The penalties are paid to the creator, otherwise they stay in the pot
+    is_early_exit_penalty_to_creator: bool,
+    // Penalties kept in the pot, they join the DAO funds
+    early_exit_penalties: Balance,
+    // Only accounts added by the creator may hold deposits
+    is_allowlist_enabled: bool,
+    allowlist: LookupSet<AccountId>,
+    // Deposits are recorded once the registry returns true on is_verified, refunded otherwise
+    attestation_registry_id: Option<AccountId>,
+    // New depositors pay for the storage of their entries, only when funding in NEAR
+    is_storage_fee_charged: bool,
+    // Storage fee paid by each depositor, refunded with the full withdrawal
+    storage_fees: LookupMap<AccountId, Balance>,
+    total_storage_fees: Balance,
+    // Bonus share weight of the deposits made during the early bonus period after funding_started_at
+    early_bonus_basis_points: u16,
+    early_bonus_period: u64,
+    funding_started_at: u64,
+    bonus_weights: LookupMap<AccountId, Balance>,
+    total_bonus_weight: Balance,
+    // Deposits are matched 1:1 from the pool funded by the sponsor, only when funding in NEAR
+    sponsor_account_id: Option<AccountId>,
+    // Matched funds are deposited in the name of the sponsor if true, else they join the DAO funds
+    is_match_in_shares: bool,
+    // Funded by the sponsor and not matched yet, it is not part of total_funds
+    match_pool: Balance,
+    matched_funds: Balance,
+    matches: LookupMap<AccountId, Balance>,
     dao_factory_account_id: AccountId,
     ft_factory_account_id: AccountId,
     metadata_url: String,
     dao_name: String,
     is_dao_created: bool,
+    is_ft_created: bool,
+    // NEP-141 token the campaign is funded with, NEAR if None
+    funding_token_id: Option<AccountId>,
+    // Funds held by the escrow that are yet to be transferred to the DAO
+    pending_dao_funds: Balance,
+    // Platform fee charged on successful delegation
+    fee_basis_points: u16,
+    fee_account_id: Option<AccountId>,
+    fee_amount: Balance,
+    // Fees, keeper rewards, early exit penalties and match withdrawals whose transfer failed,
+    // claimable by each account with withdraw_payout
+    pending_payouts: LookupMap<AccountId, Balance>,
+    status: CampaignStatus,
+    delegation_started_at: u64,
+    delegation_failed_at: u64,
+    // Funds spent by an unsuccessful delegation, deducted pro-rata from the refunds
+    delegation_costs: Balance,
 }
 
 impl Default for ConditionalEscrow {
@@ -37,33 +351,231 @@ impl Default for ConditionalEscrow {
 
 #[near_bindgen]
 impl ConditionalEscrow {
+    /// The deposit attached by the sponsor, if any, funds the match pool
     #[init]
+    #[payable]
     pub fn new(
         expires_at: u64,
         funding_amount_limit: U128,
         dao_factory_account_id: AccountId,
         ft_factory_account_id: AccountId,
         metadata_url: String,
+        config: Option<CampaignConfig>,
     ) -> Self {
         if env::state_exists() {
             env::panic_str("ERR_ALREADY_INITIALIZED");
         }
 
-        if funding_amount_limit.0 < FT_ATTACHED_DEPOSIT {
+        let CampaignConfig {
+            funding_token_id,
+            fee_basis_points,
+            fee_account_id,
+            soft_cap,
+            is_oversubscription_allowed,
+            min_deposit,
+            max_deposit_per_account,
+            creator_account_id,
+            is_position_transfer_approval_required,
+            extension_quorum_basis_points,
+            extension_threshold_basis_points,
+            tranche_basis_points,
+            milestone_verifier_account_id,
+            release_conditions,
+            release_conditions_deadline,
+            delegation_window,
+            keeper_reward_basis_points,
+            cooling_off_period,
+            early_exit_penalty_basis_points,
+            is_early_exit_penalty_to_creator,
+            is_allowlist_enabled,
+            attestation_registry_id,
+            is_storage_fee_charged,
+            early_bonus_basis_points,
+            early_bonus_period,
+            sponsor_account_id,
+            is_match_in_shares,
+        } = config.unwrap_or_default();
+
+        let fee_basis_points = fee_basis_points.unwrap_or(0);
+
+        if fee_basis_points > MAX_FEE_BASIS_POINTS {
+            env::panic_str("ERR_INVALID_FEE_BASIS_POINTS");
+        }
+
+        if fee_basis_points > 0 && fee_account_id.is_none() {
+            env::panic_str("ERR_FEE_ACCOUNT_ID_REQUIRED");
+        }
+
+        // The FT creation deposit is taken from the funds only when funding in NEAR
+        let min_funding_amount_limit = match funding_token_id {
+            Some(_) => 1,
+            None => FT_ATTACHED_DEPOSIT,
+        };
+
+        // Defaults to the hard cap
+        let soft_cap = soft_cap.unwrap_or(funding_amount_limit).0;
+
+        if soft_cap > funding_amount_limit.0 {
+            env::panic_str("ERR_SOFT_CAP_EXCEEDS_FUNDS_LIMIT");
+        }
+
+        if soft_cap < min_funding_amount_limit {
             env::panic_str("ERR_INSUFFICIENT_FUNDS_LIMIT");
         }
 
+        let min_deposit = min_deposit.map_or(0, |min_deposit| min_deposit.0);
+        let max_deposit_per_account = max_deposit_per_account.map(|max_deposit| max_deposit.0);
+
+        if min_deposit > funding_amount_limit.0 {
+            env::panic_str("ERR_MIN_DEPOSIT_EXCEEDS_FUNDS_LIMIT");
+        }
+
+        if let Some(max_deposit) = max_deposit_per_account {
+            if max_deposit == 0 || max_deposit > funding_amount_limit.0 {
+                env::panic_str("ERR_INVALID_MAX_DEPOSIT_PER_ACCOUNT");
+            }
+
+            if min_deposit > max_deposit {
+                env::panic_str("ERR_MIN_DEPOSIT_EXCEEDS_MAX_DEPOSIT");
+            }
+        }
+
+        let extension_threshold_basis_points =
+            extension_threshold_basis_points.unwrap_or(DEFAULT_EXTENSION_THRESHOLD_BASIS_POINTS);
+
+        if extension_quorum_basis_points.unwrap_or(0) > MAX_EXTENSION_BASIS_POINTS
+            || extension_threshold_basis_points >= MAX_EXTENSION_BASIS_POINTS
+        {
+            env::panic_str("ERR_INVALID_EXTENSION_BASIS_POINTS");
+        }
+
+        let tranche_basis_points = tranche_basis_points.unwrap_or_default();
+
+        if !tranche_basis_points.is_empty()
+            && (tranche_basis_points.contains(&0)
+                || tranche_basis_points
+                    .iter()
+                    .map(|basis_points| *basis_points as u32)
+                    .sum::<u32>()
+                    != MAX_TRANCHE_BASIS_POINTS as u32)
+        {
+            env::panic_str("ERR_INVALID_TRANCHES");
+        }
+
+        let release_conditions = release_conditions.unwrap_or_default();
+        let release_conditions_deadline = release_conditions_deadline.unwrap_or(0);
+
+        if !release_conditions.is_empty() && release_conditions_deadline < expires_at {
+            env::panic_str("ERR_INVALID_RELEASE_CONDITIONS_DEADLINE");
+        }
+
+        let keeper_reward_basis_points = keeper_reward_basis_points.unwrap_or(0);
+
+        if keeper_reward_basis_points > MAX_KEEPER_REWARD_BASIS_POINTS {
+            env::panic_str("ERR_INVALID_KEEPER_REWARD_BASIS_POINTS");
+        }
+
+        if early_exit_penalty_basis_points.unwrap_or(0) > MAX_EARLY_EXIT_PENALTY_BASIS_POINTS {
+            env::panic_str("ERR_INVALID_EARLY_EXIT_PENALTY_BASIS_POINTS");
+        }
+
+        let early_bonus_basis_points = early_bonus_basis_points.unwrap_or(0);
+        let early_bonus_period = early_bonus_period.unwrap_or(DEFAULT_EARLY_BONUS_PERIOD);
+
+        if early_bonus_basis_points > MAX_EARLY_BONUS_BASIS_POINTS || early_bonus_period == 0 {
+            env::panic_str("ERR_INVALID_EARLY_BONUS");
+        }
+
+        if sponsor_account_id.is_some() && funding_token_id.is_some() {
+            env::panic_str("ERR_INVALID_SPONSOR");
+        }
+
+        let match_pool = match sponsor_account_id {
+            Some(_) => env::attached_deposit(),
+            None => 0,
+        };
+
         Self {
             deposits: UnorderedMap::new(b"r".to_vec()),
+            largest_deposits: TreeMap::new(b"l".to_vec()),
             total_funds: 0,
             funding_amount_limit: funding_amount_limit.0,
+            soft_cap,
             unpaid_funding_amount: funding_amount_limit.0,
+            raised_funds: 0,
+            is_oversubscription_allowed: is_oversubscription_allowed.unwrap_or(false),
+            excess_withdrawals: LookupMap::new(b"x".to_vec()),
+            excess_dust: 0,
+            min_deposit,
+            max_deposit_per_account,
+            creator_account_id: creator_account_id.unwrap_or_else(env::signer_account_id),
+            is_position_transfer_approval_required: is_position_transfer_approval_required
+                .unwrap_or(false),
+            pending_position_transfers: LookupMap::new(b"p".to_vec()),
+            refund_cursor: 0,
+            extension_quorum_basis_points,
+            extension_threshold_basis_points,
+            deadline_extension: None,
+            deadline_extensions_count: 0,
+            extension_votes: LookupMap::new(b"v".to_vec()),
+            tranche_basis_points,
+            milestone_verifier_account_id,
+            released_tranches: 0,
+            undisbursed_funds: 0,
+            milestone_votes: LookupMap::new(b"m".to_vec()),
+            milestone_votes_for: 0,
+            milestone_votes_against: 0,
+            tranche_refunds: LookupMap::new(b"t".to_vec()),
+            tranche_refunded_allocations: 0,
+            tranche_refunded_funds: 0,
+            met_release_conditions: vec![false; release_conditions.len()],
+            release_conditions,
+            release_conditions_deadline,
+            delegation_window: delegation_window.unwrap_or(DEFAULT_DELEGATION_WINDOW),
+            keeper_reward_basis_points,
+            keeper_reward: 0,
+            keeper_account_id: None,
+            keeper_deposit: 0,
+            keeper_refunds: LookupMap::new(b"k".to_vec()),
+            cooling_off_period: cooling_off_period.unwrap_or(0),
+            cooling_off_deposits: LookupMap::new(b"c".to_vec()),
+            early_exit_penalty_basis_points,
+            is_early_exit_penalty_to_creator: is_early_exit_penalty_to_creator.unwrap_or(false),
+            early_exit_penalties: 0,
+            is_allowlist_enabled: is_allowlist_enabled.unwrap_or(false),
+            allowlist: LookupSet::new(b"w".to_vec()),
+            attestation_registry_id,
+            is_storage_fee_charged: is_storage_fee_charged.unwrap_or(false)
+                && funding_token_id.is_none(),
+            storage_fees: LookupMap::new(b"s".to_vec()),
+            total_storage_fees: 0,
+            early_bonus_basis_points,
+            early_bonus_period,
+            funding_started_at: env::block_timestamp(),
+            bonus_weights: LookupMap::new(b"b".to_vec()),
+            total_bonus_weight: 0,
+            sponsor_account_id,
+            is_match_in_shares: is_match_in_shares.unwrap_or(false),
+            match_pool,
+            matched_funds: 0,
+            matches: LookupMap::new(b"h".to_vec()),
             expires_at,
             dao_factory_account_id,
             ft_factory_account_id,
             metadata_url,
             dao_name: "".to_string(),
             is_dao_created: false,
+            is_ft_created: false,
+            funding_token_id,
+            pending_dao_funds: 0,
+            fee_basis_points,
+            fee_account_id,
+            fee_amount: 0,
+            pending_payouts: LookupMap::new(b"y".to_vec()),
+            status: CampaignStatus::Funding,
+            delegation_started_at: 0,
+            delegation_failed_at: 0,
+            delegation_costs: 0,
         }
     }
 
@@ -74,17 +586,125 @@ impl ConditionalEscrow {
         }
     }
 
+    /// Per mille of the allocated funds, rounded down. See get_share_fraction_of for the exact share
     pub fn get_shares_of(&self, payee: &AccountId) -> Balance {
-        match self.deposits.get(payee) {
-            Some(deposit) => deposit * 1000 / self.funding_amount_limit,
-            None => 0,
+        if self.total_bonus_weight > 0 {
+            let share = self.get_share_fraction_of(payee);
+
+            return (U256::from(share.numerator.0) * U256::from(1000)
+                / U256::from(share.denominator.0))
+            .as_u128();
+        }
+
+        match self.get_allocation_of(payee) {
+            0 => 0,
+            allocation => allocation * 1000 / self.get_allocated_funds(),
+        }
+    }
+
+    /// Exact share of the allocated funds, used by the FT to compute the claimable tokens.
+    /// Allocations are pro-rata to the deposits, so the share is the deposit plus its early-backer
+    /// bonus over the raised funds plus all the bonuses
+    pub fn get_share_fraction_of(&self, payee: &AccountId) -> ShareFraction {
+        ShareFraction {
+            numerator: U128(self.deposits_of(payee) + self.get_bonus_weight_of(payee)),
+            denominator: U128(self.raised_funds + self.total_bonus_weight),
+        }
+    }
+
+    pub fn get_early_bonus_basis_points(&self) -> u16 {
+        self.early_bonus_basis_points
+    }
+
+    pub fn get_early_bonus_period(&self) -> u64 {
+        self.early_bonus_period
+    }
+
+    pub fn get_funding_started_at(&self) -> u64 {
+        self.funding_started_at
+    }
+
+    /// Bonus of the deposits made now, decays linearly to 0 at the end of the early bonus period
+    pub fn get_current_bonus_basis_points(&self) -> u16 {
+        let elapsed = env::block_timestamp().saturating_sub(self.funding_started_at);
+
+        if elapsed >= self.early_bonus_period {
+            return 0;
+        }
+
+        (u128::from(self.early_bonus_basis_points) * u128::from(self.early_bonus_period - elapsed)
+            / u128::from(self.early_bonus_period)) as u16
+    }
+
+    /// Extra share weight earned by the early deposits of payee, the refunds are not affected
+    pub fn get_bonus_weight_of(&self, payee: &AccountId) -> Balance {
+        self.bonus_weights.get(payee).unwrap_or(0)
+    }
+
+    pub fn get_total_bonus_weight(&self) -> Balance {
+        self.total_bonus_weight
+    }
+
+    /// Part of the deposit that is delegated, the deposit minus its excess if oversubscribed
+    pub fn get_allocation_of(&self, payee: &AccountId) -> Balance {
+        let deposit = self.deposits_of(payee);
+
+        deposit - self.get_oversubscribed_amount(deposit)
+    }
+
+    /// Excess over the allocation that is yet to be withdrawn
+    pub fn get_excess_of(&self, payee: &AccountId) -> Balance {
+        if self.excess_withdrawals.contains_key(payee) {
+            return 0;
         }
+
+        self.get_oversubscribed_amount(self.deposits_of(payee))
     }
 
+    /// All the deposits, use get_deposits_paginated for campaigns with many depositors
     pub fn get_deposits(&self) -> Vec<(AccountId, Balance)> {
         self.deposits.to_vec()
     }
 
+    /// Depositors with a balance, the withdrawn ones keep a zeroed deposit entry
+    pub fn get_depositors_count(&self) -> u64 {
+        self.largest_deposits.len()
+    }
+
+    /// Get deposits in paginated view.
+    pub fn get_deposits_paginated(&self, from_index: u64, limit: u64) -> Vec<(AccountId, Balance)> {
+        let keys = self.deposits.keys_as_vector();
+        let values = self.deposits.values_as_vector();
+
+        (from_index..std::cmp::min(from_index.saturating_add(limit), keys.len()))
+            .filter_map(|index| Some((keys.get(index)?, values.get(index)?)))
+            .collect()
+    }
+
+    /// Totals, depositors, time remaining and status in a single view
+    pub fn get_campaign_summary(&self) -> CampaignSummary {
+        let (largest_deposit, largest_depositor) = self
+            .largest_deposits
+            .max()
+            .map_or((0, None), |(balance, account_id)| {
+                (balance, Some(account_id))
+            });
+
+        CampaignSummary {
+            status: self.get_status(),
+            total_funds: U128(self.total_funds),
+            raised_funds: U128(self.raised_funds),
+            funding_amount_limit: U128(self.funding_amount_limit),
+            soft_cap: U128(self.soft_cap),
+            unpaid_funding_amount: U128(self.unpaid_funding_amount),
+            depositors_count: self.get_depositors_count(),
+            largest_depositor,
+            largest_deposit: U128(largest_deposit),
+            expires_at: self.expires_at,
+            time_remaining: self.expires_at.saturating_sub(env::block_timestamp()),
+        }
+    }
+
     pub fn get_total_funds(&self) -> Balance {
         self.total_funds
     }
@@ -101,874 +721,9497 @@ impl ConditionalEscrow {
         self.funding_amount_limit
     }
 
+    pub fn get_soft_cap(&self) -> u128 {
+        self.soft_cap
+    }
+
     pub fn get_unpaid_funding_amount(&self) -> u128 {
         self.unpaid_funding_amount
     }
 
-    pub fn get_dao_factory_account_id(&self) -> AccountId {
-        self.dao_factory_account_id.clone()
+    /// Sum of the deposits, unlike total_funds it is kept after the funds are delegated
+    pub fn get_raised_funds(&self) -> u128 {
+        self.raised_funds
     }
 
-    pub fn get_ft_factory_account_id(&self) -> AccountId {
-        self.ft_factory_account_id.clone()
+    /// Raised funds up to the funding_amount_limit, the amount that is delegated
+    pub fn get_allocated_funds(&self) -> u128 {
+        std::cmp::min(self.raised_funds, self.funding_amount_limit)
     }
 
-    pub fn get_dao_name(&self) -> String {
-        self.dao_name.clone()
+    pub fn is_oversubscription_allowed(&self) -> bool {
+        self.is_oversubscription_allowed
     }
 
-    pub fn is_deposit_allowed(&self) -> bool {
-        !self.has_contract_expired() && !self.is_funding_reached()
+    pub fn get_min_deposit(&self) -> Balance {
+        self.min_deposit
     }
 
-    pub fn is_withdrawal_allowed(&self) -> bool {
-        self.has_contract_expired() && !self.is_funding_reached()
+    pub fn get_max_deposit_per_account(&self) -> Option<Balance> {
+        self.max_deposit_per_account
     }
 
-    #[payable]
-    pub fn deposit(&mut self) {
-        if env::current_account_id() == env::signer_account_id() {
-            env::panic_str("ERR_OWNER_SHOULD_NOT_DEPOSIT");
-        }
+    pub fn get_creator_account_id(&self) -> AccountId {
+        self.creator_account_id.clone()
+    }
 
-        if env::attached_deposit() == 0 {
-            env::panic_str("ERR_DEPOSIT_SHOULD_NOT_BE_0");
-        }
+    pub fn is_position_transfer_approval_required(&self) -> bool {
+        self.is_position_transfer_approval_required
+    }
 
-        if !self.is_deposit_allowed() {
-            env::panic_str("ERR_DEPOSIT_NOT_ALLOWED");
-        }
+    /// Receiver and amount of the position transfer requested by sender_id
+    pub fn get_pending_position_transfer(&self, sender_id: AccountId) -> Option<(AccountId, U128)> {
+        self.pending_position_transfers
+            .get(&sender_id)
+            .map(|(receiver_id, amount)| (receiver_id, U128(amount)))
+    }
 
-        if env::attached_deposit() > self.get_unpaid_funding_amount() {
-            env::panic_str("ERR_DEPOSIT_NOT_ALLOWED");
-        }
+    pub fn get_refund_cursor(&self) -> u64 {
+        self.refund_cursor
+    }
 
-        let amount = env::attached_deposit();
-        let payee = env::signer_account_id();
-        let current_balance = self.deposits_of(&payee);
-        let new_balance = &(current_balance.wrapping_add(amount));
+    pub fn get_extension_quorum_basis_points(&self) -> Option<u16> {
+        self.extension_quorum_basis_points
+    }
 
-        self.deposits.insert(&payee, new_balance);
-        self.total_funds = self.total_funds.wrapping_add(amount);
-        self.unpaid_funding_amount = self.unpaid_funding_amount.wrapping_sub(amount);
+    pub fn get_extension_threshold_basis_points(&self) -> u16 {
+        self.extension_threshold_basis_points
+    }
 
-        log!(
-            "{} deposited {} NEAR tokens. New balance {} — Total funds: {} — Unpaid funds: {}",
-            &payee,
-            amount,
-            new_balance,
-            self.total_funds,
-            self.unpaid_funding_amount
-        );
-        // @TODO emit deposit event
+    pub fn get_deadline_extension(&self) -> Option<DeadlineExtension> {
+        self.deadline_extension.clone()
     }
 
-    #[payable]
-    pub fn withdraw(&mut self) {
-        if !self.is_withdrawal_allowed() {
-            env::panic_str("ERR_WITHDRAWAL_NOT_ALLOWED");
-        }
+    /// Vote of payee on the current deadline extension
+    pub fn get_extension_vote_of(&self, payee: AccountId) -> Option<bool> {
+        self.get_current_extension_vote(&payee)
+    }
 
-        let payee = env::signer_account_id();
-        let payment = self.deposits_of(&payee);
+    pub fn get_tranche_basis_points(&self) -> Vec<u16> {
+        self.tranche_basis_points.clone()
+    }
 
-        Promise::new(payee.clone()).transfer(payment);
-        self.deposits.insert(&payee, &0);
-        self.total_funds = self.total_funds.wrapping_sub(payment);
-        self.unpaid_funding_amount = self.unpaid_funding_amount.wrapping_add(payment);
+    pub fn get_milestone_verifier_account_id(&self) -> Option<AccountId> {
+        self.milestone_verifier_account_id.clone()
+    }
 
-        log!(
-            "{} withdrawn {} NEAR tokens. New balance {} — Total funds: {} — Unpaid funds: {}",
-            &payee,
-            payment,
-            self.deposits_of(&payee),
-            self.total_funds,
-            self.unpaid_funding_amount
-        );
-        // @TODO emit withdraw event
+    pub fn get_released_tranches(&self) -> u32 {
+        self.released_tranches
     }
 
-    #[payable]
-    pub fn delegate_funds(&mut self, dao_name: String) -> Promise {
-        if self.is_deposit_allowed() || self.is_withdrawal_allowed() {
-            env::panic_str("ERR_DELEGATE_NOT_ALLOWED");
-        }
+    pub fn get_undisbursed_funds(&self) -> Balance {
+        self.undisbursed_funds
+    }
+
+    /// Allocations voting for and against the pending milestone
+    pub fn get_milestone_votes(&self) -> (U128, U128) {
+        (
+            U128(self.milestone_votes_for),
+            U128(self.milestone_votes_against),
+        )
+    }
 
-        if self.total_funds.checked_sub(FT_ATTACHED_DEPOSIT) == None {
-            env::panic_str("ERR_TOTAL_FUNDS_OVERFLOW");
+    /// Vote of payee on the pending milestone
+    pub fn get_milestone_vote_of(&self, payee: AccountId) -> Option<bool> {
+        match self.milestone_votes.get(&payee) {
+            Some((milestone, approve)) if milestone == self.released_tranches => Some(approve),
+            _ => None,
         }
+    }
 
-        // @TODO charge a fee here (1.5% initially?) when a property is sold by our contract
+    pub fn get_tranche_refund_of(&self, payee: &AccountId) -> Balance {
+        self.tranche_refunds.get(payee).unwrap_or(0)
+    }
 
-        let dao_promise = Promise::new(self.dao_factory_account_id.clone()).function_call(
-            "create_dao".to_string(),
-            json!({"dao_name": dao_name.clone(), "deposits": self.get_deposit_accounts() })
-                .to_string()
-                .into_bytes(),
-            self.total_funds - FT_ATTACHED_DEPOSIT,
-            GAS_FOR_CREATE_DAO,
-        );
+    pub fn get_release_conditions(&self) -> Vec<ReleaseCondition> {
+        self.release_conditions.clone()
+    }
 
-        let ft_promise = Promise::new(self.ft_factory_account_id.clone()).function_call(
-            "create_ft".to_string(),
-            json!({"name": dao_name.clone()}).to_string().into_bytes(),
-            FT_ATTACHED_DEPOSIT,
-            GAS_FOR_CREATE_FT,
-        );
+    /// Whether each release condition was met, in the order of get_release_conditions
+    pub fn get_met_release_conditions(&self) -> Vec<bool> {
+        self.met_release_conditions.clone()
+    }
 
-        let callback = Promise::new(env::current_account_id()).function_call(
-            "on_delegate_callback".to_string(),
-            json!({"dao_name": dao_name.clone()})
-                .to_string()
-                .into_bytes(),
-            0,
-            GAS_FOR_CALLBACK,
-        );
+    pub fn get_release_conditions_deadline(&self) -> u64 {
+        self.release_conditions_deadline
+    }
 
-        dao_promise.and(ft_promise).then(callback)
+    pub fn are_release_conditions_met(&self) -> bool {
+        self.met_release_conditions.iter().all(|is_met| *is_met)
+    }
 
-        // @TODO emit delegate_funds event
+    /// Release conditions can be met until the delegation or the release_conditions_deadline
+    pub fn is_release_condition_check_allowed(&self) -> bool {
+        !self.release_conditions.is_empty()
+            && !self.has_release_conditions_deadline_passed()
+            && matches!(
+                self.get_status(),
+                CampaignStatus::Funding | CampaignStatus::Funded
+            )
     }
 
-    #[private]
-    pub fn on_delegate_callback(&mut self, dao_name: String) -> bool {
-        if env::promise_results_count() != 2 {
-            env::panic_str("ERR_CALLBACK_METHOD");
-        }
+    pub fn get_delegation_window(&self) -> u64 {
+        self.delegation_window
+    }
 
-        let on_create_dao_successful;
-        let on_create_ft_successful;
+    /// delegate_funds must be called by then, otherwise the campaign expires and depositors may
+    /// withdraw
+    pub fn get_delegation_deadline(&self) -> u64 {
+        std::cmp::max(self.expires_at, self.release_conditions_deadline)
+            .saturating_add(self.delegation_window)
+    }
 
-        // Create DAO Contract
-        match env::promise_result(0) {
-            PromiseResult::Successful(result) => {
-                let res: bool = near_sdk::serde_json::from_slice(&result).unwrap();
-
-                if res {
-                    self.total_funds = 0;
-                    self.dao_name = dao_name;
-                    self.is_dao_created = true;
-                    on_create_dao_successful = true;
-                } else {
-                    on_create_dao_successful = false;
-                }
-            }
-            _ => env::panic_str("ERR_CREATE_DAO_UNSUCCESSFUL"),
-        }
+    pub fn get_keeper_reward_basis_points(&self) -> u16 {
+        self.keeper_reward_basis_points
+    }
 
-        // Create FT Contract
-        match env::promise_result(1) {
-            PromiseResult::Successful(result) => {
-                let res: bool = near_sdk::serde_json::from_slice(&result).unwrap();
+    /// Keeper reward reserved while delegating and paid once the delegation succeeds
+    pub fn get_keeper_reward(&self) -> Balance {
+        self.keeper_reward
+    }
 
-                if res {
-                    on_create_ft_successful = true;
-                } else {
-                    on_create_ft_successful = false;
-                }
-            }
-            _ => env::panic_str("ERR_CREATE_FT_UNSUCCESSFUL"),
-        }
+    pub fn get_keeper_account_id(&self) -> Option<AccountId> {
+        self.keeper_account_id.clone()
+    }
 
-        on_create_dao_successful && on_create_ft_successful
+    pub fn get_cooling_off_period(&self) -> u64 {
+        self.cooling_off_period
     }
 
-    fn has_contract_expired(&self) -> bool {
-        self.expires_at < env::block_timestamp().try_into().unwrap()
+    pub fn get_early_exit_penalty_basis_points(&self) -> Option<u16> {
+        self.early_exit_penalty_basis_points
     }
 
-    fn is_funding_reached(&self) -> bool {
-        self.get_total_funds() >= self.get_funding_amount_limit()
+    pub fn is_early_exit_penalty_to_creator(&self) -> bool {
+        self.is_early_exit_penalty_to_creator
     }
 
-    fn get_deposit_accounts(&self) -> Vec<String> {
-        let mut accounts = vec![];
+    pub fn get_early_exit_penalties(&self) -> Balance {
+        self.early_exit_penalties
+    }
 
-        for i in self.deposits.to_vec() {
-            accounts.push(i.0.to_string());
-        }
+    pub fn get_sponsor_account_id(&self) -> Option<AccountId> {
+        self.sponsor_account_id.clone()
+    }
 
-        accounts
+    pub fn is_match_in_shares(&self) -> bool {
+        self.is_match_in_shares
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use chrono::Utc;
-    use near_sdk::test_utils::test_env::{alice, bob, carol};
-    use near_sdk::test_utils::{accounts, VMContextBuilder};
-    use near_sdk::{testing_env, PromiseResult};
+    pub fn get_match_pool(&self) -> Balance {
+        self.match_pool
+    }
 
-    const ATTACHED_DEPOSIT: Balance = 1_000_000_000_000_000_000_000_000; // 1 Near
-    const MIN_FUNDING_AMOUNT: Balance = 15_000_000_000_000_000_000_000_000; // 15 Near
+    pub fn get_matched_funds(&self) -> Balance {
+        self.matched_funds
+    }
 
-    fn setup_context() -> VMContextBuilder {
-        let mut context = VMContextBuilder::new();
-        let now = Utc::now().timestamp_subsec_nanos();
-        testing_env!(context
-            .predecessor_account_id(alice())
-            .block_timestamp(now.try_into().unwrap())
-            .build());
+    /// Amount matched by the sponsor to the deposits of payee
+    pub fn get_match_of(&self, payee: &AccountId) -> Balance {
+        self.matches.get(payee).unwrap_or(0)
+    }
 
-        context
+    /// Deposits of payee still in their cooling-off period
+    pub fn get_cooling_off_amount_of(&self, payee: &AccountId) -> Balance {
+        self.get_cooling_off_deposits_of(payee)
+            .iter()
+            .map(|(_, amount)| amount)
+            .sum()
     }
 
-    fn setup_contract(expires_at: u64, funding_amount_limit: u128) -> ConditionalEscrow {
-        let contract = ConditionalEscrow::new(
-            expires_at,
-            U128(funding_amount_limit),
-            accounts(3),
-            accounts(4),
-            "metadata_url.json".to_string(),
-        );
+    /// Penalty charged if payee withdraws now with withdraw_early, None if it is not allowed. Once
+    /// the hard cap is reached, only the cooling-off amount may be withdrawn, without a penalty
+    pub fn get_early_exit_penalty_of(&self, payee: &AccountId) -> Option<Balance> {
+        match self.get_status() {
+            CampaignStatus::Funding => (),
+            CampaignStatus::Funded
+                if !self.has_contract_expired() && self.get_cooling_off_amount_of(payee) > 0 =>
+            {
+                return Some(0)
+            }
+            _ => return None,
+        }
 
-        contract
+        let balance = self.deposits_of(payee);
+
+        match (
+            balance.saturating_sub(self.get_cooling_off_amount_of(payee)),
+            self.early_exit_penalty_basis_points,
+        ) {
+            (0, _) => Some(0),
+            (_, None) => None,
+            (penalized_amount, Some(basis_points)) => Some(
+                (U256::from(penalized_amount) * U256::from(basis_points)
+                    / U256::from(MAX_EARLY_EXIT_PENALTY_BASIS_POINTS))
+                .as_u128(),
+            ),
+        }
     }
 
-    fn add_expires_at_nanos(offset: u32) -> u64 {
-        let now = Utc::now().timestamp_subsec_nanos();
-        (now + offset).into()
+    pub fn is_allowlist_enabled(&self) -> bool {
+        self.is_allowlist_enabled
     }
 
-    fn substract_expires_at_nanos(offset: u32) -> u64 {
-        let now = Utc::now().timestamp_subsec_nanos();
-        (now - offset).into()
+    pub fn is_allowlisted(&self, account_id: AccountId) -> bool {
+        self.allowlist.contains(&account_id)
     }
 
-    #[test]
-    #[should_panic(expected = "ERR_INSUFFICIENT_FUNDS_LIMIT")]
-    fn test_new_fail() {
-        let mut context = setup_context();
+    pub fn get_attestation_registry_id(&self) -> Option<AccountId> {
+        self.attestation_registry_id.clone()
+    }
 
-        testing_env!(context.signer_account_id(bob()).attached_deposit(0).build());
+    pub fn is_storage_fee_charged(&self) -> bool {
+        self.is_storage_fee_charged
+    }
 
-        let expires_at = add_expires_at_nanos(100);
+    pub fn get_storage_fee_of(&self, payee: &AccountId) -> Balance {
+        self.storage_fees.get(payee).unwrap_or(0)
+    }
 
-        // Should fail because insufficient funds limit
-        ConditionalEscrow::new(
-            expires_at,
-            U128(1_000_000_000_000_000_000_000_000), // 1 NEAR
-            accounts(3),
-            accounts(4),
-            "metadata_url.json".to_string(),
-        );
+    pub fn get_total_storage_fees(&self) -> Balance {
+        self.total_storage_fees
     }
 
-    #[test]
-    fn test_get_deposits_of() {
-        let expires_at = add_expires_at_nanos(100);
+    /// Balance locked by the storage used by the escrow, it is never forwarded to the DAO
+    pub fn get_storage_reserve(&self) -> Balance {
+        Balance::from(env::storage_usage()) * env::storage_byte_cost()
+    }
 
-        let contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+    /// Delegated campaigns with tranches left to release wait for the approval of a milestone
+    pub fn is_milestone_pending(&self) -> bool {
+        self.get_status() == CampaignStatus::Delegated
+            && self.released_tranches > 0
+            && (self.released_tranches as usize) < self.tranche_basis_points.len()
+    }
 
-        assert_eq!(
+    /// The creator may cancel the campaign until the DAO is created
+    pub fn is_cancel_allowed(&self) -> bool {
+        match self.get_status() {
+            CampaignStatus::Funding | CampaignStatus::Funded => true,
+            CampaignStatus::DelegationFailed => !self.is_dao_created,
+            _ => false,
+        }
+    }
+
+    /// Positions can be transferred until the campaign closes. Receivers cannot be verified
+    /// synchronously by an attestation registry, so it disables the transfers
+    pub fn is_position_transfer_allowed(&self) -> bool {
+        self.get_status() == CampaignStatus::Funding && self.attestation_registry_id.is_none()
+    }
+
+    pub fn get_dao_factory_account_id(&self) -> AccountId {
+        self.dao_factory_account_id.clone()
+    }
+
+    pub fn get_ft_factory_account_id(&self) -> AccountId {
+        self.ft_factory_account_id.clone()
+    }
+
+    pub fn get_dao_name(&self) -> String {
+        self.dao_name.clone()
+    }
+
+    pub fn get_funding_token_id(&self) -> Option<AccountId> {
+        self.funding_token_id.clone()
+    }
+
+    pub fn get_keeper_refund_of(&self, account_id: &AccountId) -> Balance {
+        self.keeper_refunds.get(account_id).unwrap_or(0)
+    }
+
+    pub fn get_pending_dao_funds(&self) -> Balance {
+        self.pending_dao_funds
+    }
+
+    pub fn get_fee_basis_points(&self) -> u16 {
+        self.fee_basis_points
+    }
+
+    pub fn get_fee_account_id(&self) -> Option<AccountId> {
+        self.fee_account_id.clone()
+    }
+
+    /// Fee reserved while delegating and charged once the delegation succeeds
+    pub fn get_fee_amount(&self) -> Balance {
+        self.fee_amount
+    }
+
+    /// Payouts of account_id whose transfer failed, see withdraw_payout
+    pub fn get_pending_payout_of(&self, account_id: &AccountId) -> Balance {
+        self.pending_payouts.get(account_id).unwrap_or(0)
+    }
+
+    /// Persisted status, including the time based transitions that are yet to be persisted
+    pub fn get_status(&self) -> CampaignStatus {
+        let status = match self.status {
+            CampaignStatus::Funding if self.has_contract_expired() => {
+                match self.is_soft_cap_reached() {
+                    true => CampaignStatus::Funded,
+                    false => CampaignStatus::Expired,
+                }
+            }
+            CampaignStatus::Delegating if self.has_delegation_timed_out() => {
+                CampaignStatus::DelegationFailed
+            }
+            status => status,
+        };
+
+        match status {
+            CampaignStatus::Funded
+                if self.get_delegation_deadline() < env::block_timestamp()
+                    || (self.has_release_conditions_deadline_passed()
+                        && !self.are_release_conditions_met()) =>
+            {
+                CampaignStatus::Expired
+            }
+            status => status,
+        }
+    }
+
+    pub fn is_deposit_allowed(&self) -> bool {
+        self.get_status() == CampaignStatus::Funding
+    }
+
+    pub fn is_withdrawal_allowed(&self) -> bool {
+        match self.get_status() {
+            CampaignStatus::Expired
+            | CampaignStatus::Cancelled
+            | CampaignStatus::MilestoneRejected => true,
+            CampaignStatus::DelegationFailed => {
+                !self.is_dao_created && self.has_delegation_retry_period_ended()
+            }
+            _ => false,
+        }
+    }
+
+    /// Oversubscribed campaigns that proceed refund the excess over the allocations
+    pub fn is_excess_withdrawal_allowed(&self) -> bool {
+        self.is_oversubscription_allowed
+            && match self.get_status() {
+                CampaignStatus::Funded
+                | CampaignStatus::Delegating
+                | CampaignStatus::Delegated
+                | CampaignStatus::MilestoneRejected => true,
+                CampaignStatus::DelegationFailed => !self.is_withdrawal_allowed(),
+                _ => false,
+            }
+    }
+
+    pub fn is_delegation_allowed(&self) -> bool {
+        match self.get_status() {
+            CampaignStatus::Funded => self.are_release_conditions_met(),
+            CampaignStatus::DelegationFailed => {
+                self.is_dao_created || !self.has_delegation_retry_period_ended()
+            }
+            _ => false,
+        }
+    }
+
+    pub fn is_ft_created(&self) -> bool {
+        self.is_ft_created
+    }
+
+    /// A delegation that timed out failed at the end of the DELEGATION_CALLBACK_TIMEOUT
+    pub fn get_delegation_failed_at(&self) -> u64 {
+        match self.status {
+            CampaignStatus::Delegating if self.has_delegation_timed_out() => {
+                self.delegation_started_at + DELEGATION_CALLBACK_TIMEOUT
+            }
+            _ => self.delegation_failed_at,
+        }
+    }
+
+    pub fn get_delegation_costs(&self) -> Balance {
+        self.delegation_costs
+    }
+
+    #[payable]
+    pub fn deposit(&mut self) {
+        self.deposit_for(env::predecessor_account_id());
+    }
+
+    /// Deposits the attached NEAR on behalf of beneficiary, the surplus is refunded to the caller
+    #[payable]
+    pub fn deposit_for(&mut self, beneficiary: AccountId) {
+        if self.funding_token_id.is_some() {
+            env::panic_str("ERR_FUNDING_TOKEN_REQUIRED");
+        }
+
+        if self.attestation_registry_id.is_some() {
+            self.verify_deposit(
+                beneficiary,
+                env::attached_deposit(),
+                env::predecessor_account_id(),
+            );
+
+            return;
+        }
+
+        let surplus = self.internal_deposit(beneficiary, env::attached_deposit());
+
+        if surplus > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(surplus);
+        }
+    }
+
+    /// NEP-141 receiver, deposits the transferred funding tokens on behalf of sender_id, or of
+    /// the beneficiary account id given in msg
+    pub fn ft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        if self.funding_token_id != Some(env::predecessor_account_id()) {
+            env::panic_str("ERR_INVALID_FUNDING_TOKEN");
+        }
+
+        let beneficiary = match msg.is_empty() {
+            true => sender_id.clone(),
+            false => match msg.parse::<AccountId>() {
+                Ok(beneficiary) => beneficiary,
+                Err(_) => env::panic_str("ERR_INVALID_BENEFICIARY"),
+            },
+        };
+
+        if self.attestation_registry_id.is_some() {
+            return PromiseOrValue::Promise(self.verify_deposit(beneficiary, amount.0, sender_id));
+        }
+
+        // The surplus is returned to the sender as the unused amount
+        let surplus = self.internal_deposit(beneficiary, amount.0);
+
+        PromiseOrValue::Value(U128(surplus))
+    }
+
+    /// Records the deposit if the attestation registry verified payee, otherwise refunds it to
+    /// sender_id. Returns the unused amount, refunded by ft_resolve_transfer for NEP-141 deposits
+    #[private]
+    pub fn on_verify_deposit_callback(
+        &mut self,
+        payee: AccountId,
+        amount: U128,
+        sender_id: AccountId,
+    ) -> U128 {
+        self.update_status();
+
+        let is_verified = env::promise_results_count() == 1
+            && Self::is_promise_result_true(&env::promise_result(0));
+
+        // The checks are repeated, the campaign may have changed since the deposit. The funds were
+        // already received, so any failure refunds them instead of panicking
+        let unused_amount = match is_verified {
+            true => match self.try_internal_deposit(payee.clone(), amount.0) {
+                Ok(surplus) => surplus,
+                Err(err) => {
+                    log!("{}: {} deposit refunded", err, &payee);
+                    amount.0
+                }
+            },
+            false => {
+                log!("ERR_NOT_VERIFIED: {} deposit refunded", &payee);
+                amount.0
+            }
+        };
+
+        if unused_amount > 0 && self.funding_token_id.is_none() {
+            Promise::new(sender_id).transfer(unused_amount);
+        }
+
+        U128(unused_amount)
+    }
+
+    /// Adds accounts to the allowlist, only the creator may manage it
+    pub fn add_to_allowlist(&mut self, account_ids: Vec<AccountId>) {
+        if env::predecessor_account_id() != self.creator_account_id {
+            env::panic_str("ERR_NOT_CREATOR");
+        }
+
+        for account_id in account_ids {
+            self.allowlist.insert(&account_id);
+        }
+    }
+
+    /// Removes accounts from the allowlist, their deposits are kept
+    pub fn remove_from_allowlist(&mut self, account_ids: Vec<AccountId>) {
+        if env::predecessor_account_id() != self.creator_account_id {
+            env::panic_str("ERR_NOT_CREATOR");
+        }
+
+        for account_id in account_ids {
+            self.allowlist.remove(&account_id);
+        }
+    }
+
+    /// Transfers amount of the caller deposit to receiver_id. If the approval of the creator is
+    /// required, the transfer is requested and executed by approve_position_transfer
+    pub fn transfer_position(&mut self, receiver_id: AccountId, amount: U128) {
+        self.update_status();
+
+        let sender_id = env::predecessor_account_id();
+
+        if receiver_id == sender_id || receiver_id == env::current_account_id() {
+            env::panic_str("ERR_INVALID_POSITION_RECEIVER");
+        }
+
+        if !self.is_position_transfer_approval_required {
+            return self.internal_transfer_position(sender_id, receiver_id, amount.0);
+        }
+
+        if !self.is_position_transfer_allowed() {
+            env::panic_str("ERR_POSITION_TRANSFER_NOT_ALLOWED");
+        }
+
+        if amount.0 == 0 || amount.0 > self.deposits_of(&sender_id) {
+            env::panic_str("ERR_INVALID_POSITION_AMOUNT");
+        }
+
+        // Replaces any previous request of the sender
+        self.pending_position_transfers
+            .insert(&sender_id, &(receiver_id.clone(), amount.0));
+
+        EscrowEvent::PositionTransferRequested(PositionTransferRequestedEvent {
+            sender_id,
+            receiver_id,
+            amount,
+        })
+        .emit();
+    }
+
+    /// Executes the position transfer requested by sender_id, only the creator may approve it
+    pub fn approve_position_transfer(&mut self, sender_id: AccountId) {
+        self.update_status();
+
+        if env::predecessor_account_id() != self.creator_account_id {
+            env::panic_str("ERR_NOT_CREATOR");
+        }
+
+        let (receiver_id, amount) = match self.pending_position_transfers.remove(&sender_id) {
+            Some(transfer) => transfer,
+            None => env::panic_str("ERR_NO_PENDING_POSITION_TRANSFER"),
+        };
+
+        self.internal_transfer_position(sender_id, receiver_id, amount);
+    }
+
+    /// Stops the campaign, deposits and delegate_funds are blocked and depositors may withdraw
+    pub fn cancel(&mut self) {
+        self.update_status();
+
+        if env::predecessor_account_id() != self.creator_account_id {
+            env::panic_str("ERR_NOT_CREATOR");
+        }
+
+        if !self.is_cancel_allowed() {
+            env::panic_str("ERR_CANCEL_NOT_ALLOWED");
+        }
+
+        self.set_status(CampaignStatus::Cancelled);
+
+        log!(
+            "{} cancelled the campaign. Total funds: {}",
+            &self.creator_account_id,
+            self.total_funds
+        );
+    }
+
+    /// Proposes to move expires_at, only the creator may propose it. Replaces any previous
+    /// proposal and its votes
+    pub fn propose_deadline_extension(&mut self, expires_at: u64) {
+        self.update_status();
+
+        if env::predecessor_account_id() != self.creator_account_id {
+            env::panic_str("ERR_NOT_CREATOR");
+        }
+
+        if self.extension_quorum_basis_points.is_none() {
+            env::panic_str("ERR_DEADLINE_EXTENSION_NOT_ALLOWED");
+        }
+
+        if self.get_status() != CampaignStatus::Funding {
+            env::panic_str("ERR_DEADLINE_EXTENSION_NOT_ALLOWED");
+        }
+
+        if expires_at <= self.expires_at
+            || (!self.release_conditions.is_empty()
+                && expires_at > self.release_conditions_deadline)
+        {
+            env::panic_str("ERR_INVALID_DEADLINE_EXTENSION");
+        }
+
+        self.deadline_extensions_count += 1;
+        self.deadline_extension = Some(DeadlineExtension {
+            expires_at,
+            votes_for: U128(0),
+            votes_against: U128(0),
+        });
+
+        EscrowEvent::DeadlineExtensionProposed(DeadlineExtensionProposedEvent {
+            expires_at,
+            current_expires_at: self.expires_at,
+        })
+        .emit();
+    }
+
+    /// Votes on the deadline extension with the weight of the caller deposit. The deadline is
+    /// extended once the quorum is reached and the votes in favour exceed the threshold
+    pub fn vote_deadline_extension(&mut self, approve: bool) {
+        self.update_status();
+
+        let mut extension = match &self.deadline_extension {
+            Some(extension) if self.get_status() == CampaignStatus::Funding => extension.clone(),
+            _ => env::panic_str("ERR_NO_DEADLINE_EXTENSION"),
+        };
+
+        let account_id = env::predecessor_account_id();
+        let weight = self.deposits_of(&account_id);
+
+        if weight == 0 {
+            env::panic_str("ERR_ZERO_BALANCE");
+        }
+
+        // Replaces any previous vote of the account
+        match self.get_current_extension_vote(&account_id) {
+            Some(true) => extension.votes_for.0 -= weight,
+            Some(false) => extension.votes_against.0 -= weight,
+            None => {}
+        }
+
+        match approve {
+            true => extension.votes_for.0 += weight,
+            false => extension.votes_against.0 += weight,
+        }
+
+        self.extension_votes
+            .insert(&account_id, &(self.deadline_extensions_count, approve));
+
+        EscrowEvent::DeadlineExtensionVoted(DeadlineExtensionVotedEvent {
+            account_id,
+            approve,
+            weight: U128(weight),
+            votes_for: extension.votes_for,
+            votes_against: extension.votes_against,
+        })
+        .emit();
+
+        if !self.is_deadline_extension_approved(&extension) {
+            self.deadline_extension = Some(extension);
+            return;
+        }
+
+        log!(
+            "Funding deadline extended from {} to {}",
+            self.expires_at,
+            extension.expires_at
+        );
+
+        EscrowEvent::DeadlineExtended(DeadlineExtendedEvent {
+            previous_expires_at: self.expires_at,
+            expires_at: extension.expires_at,
+        })
+        .emit();
+
+        self.expires_at = extension.expires_at;
+        self.deadline_extension = None;
+    }
+
+    /// Meets the attestation conditions of the caller
+    pub fn attest(&mut self) {
+        self.update_status();
+
+        if !self.is_release_condition_check_allowed() {
+            env::panic_str("ERR_RELEASE_CONDITION_CHECK_NOT_ALLOWED");
+        }
+
+        let account_id = env::predecessor_account_id();
+        let mut is_attester = false;
+
+        for index in 0..self.release_conditions.len() {
+            if let ReleaseCondition::Attestation {
+                account_id: attester_id,
+            } = &self.release_conditions[index]
+            {
+                if *attester_id == account_id {
+                    is_attester = true;
+                    self.set_release_condition_met(index as u32);
+                }
+            }
+        }
+
+        if !is_attester {
+            env::panic_str("ERR_NOT_ATTESTER");
+        }
+    }
+
+    /// Calls the oracle of the release condition at index, it is met if the oracle returns true
+    pub fn check_release_condition(&mut self, index: u32) -> Promise {
+        self.update_status();
+
+        if !self.is_release_condition_check_allowed() {
+            env::panic_str("ERR_RELEASE_CONDITION_CHECK_NOT_ALLOWED");
+        }
+
+        let (contract_id, method_name, args) = match self.release_conditions.get(index as usize) {
+            Some(ReleaseCondition::Oracle {
+                contract_id,
+                method_name,
+                args,
+            }) => (contract_id.clone(), method_name.clone(), args.clone()),
+            _ => env::panic_str("ERR_INVALID_RELEASE_CONDITION"),
+        };
+
+        Promise::new(contract_id)
+            .function_call(method_name, args.into_bytes(), 0, GAS_FOR_RELEASE_CONDITION)
+            .then(Promise::new(env::current_account_id()).function_call(
+                "on_release_condition_callback".to_string(),
+                json!({ "index": index }).to_string().into_bytes(),
+                0,
+                GAS_FOR_CALLBACK,
+            ))
+    }
+
+    #[private]
+    pub fn on_release_condition_callback(&mut self, index: u32) -> bool {
+        let is_met = env::promise_results_count() == 1
+            && Self::is_promise_result_true(&env::promise_result(0));
+
+        if !is_met || !self.is_release_condition_check_allowed() {
+            log!("Release condition {} not met", index);
+
+            return false;
+        }
+
+        self.set_release_condition_met(index);
+
+        true
+    }
+
+    #[payable]
+    pub fn withdraw(&mut self) {
+        self.update_status();
+
+        if !self.is_withdrawal_allowed() {
+            env::panic_str("ERR_WITHDRAWAL_NOT_ALLOWED");
+        }
+
+        let payee = env::predecessor_account_id();
+
+        if !self.is_refund_pending(&payee) {
+            env::panic_str("ERR_ZERO_BALANCE");
+        }
+
+        self.internal_withdraw(payee);
+    }
+
+    /// Withdraws the full balance of the caller during funding. Deposits still in their
+    /// cooling-off period are withdrawn in full, the rest is charged the early exit penalty
+    pub fn withdraw_early(&mut self) {
+        self.update_status();
+
+        let payee = env::predecessor_account_id();
+        let balance = self.deposits_of(&payee);
+
+        if balance == 0 {
+            env::panic_str("ERR_ZERO_BALANCE");
+        }
+
+        // The funds matched in the name of the sponsor follow the deposits they match
+        if self.is_sponsor_position(&payee) {
+            env::panic_str("ERR_EARLY_WITHDRAWAL_NOT_ALLOWED");
+        }
+
+        let penalty = match self.get_early_exit_penalty_of(&payee) {
+            Some(penalty) => penalty,
+            None => env::panic_str("ERR_EARLY_WITHDRAWAL_NOT_ALLOWED"),
+        };
+
+        // Once funded, only the deposits still in their cooling-off period are withdrawn
+        let is_funded = self.status == CampaignStatus::Funded;
+        let amount = match is_funded {
+            true => self.get_cooling_off_amount_of(&payee),
+            false => balance,
+        };
+        let remaining_balance = balance - amount;
+        let payment = amount - penalty;
+
+        let initial_storage_usage = env::storage_usage();
+
+        // A failed transfer restores the payment, the deposits still in their cooling-off period
+        // and the storage fee. The penalty was already charged and the early-backer bonus and the
+        // match of the withdrawn amount are forfeited
+        self.forfeit_bonus_weight(&payee, amount, balance);
+        self.unmatch_deposit(
+            &payee,
+            match remaining_balance {
+                0 => self.get_match_of(&payee),
+                _ => amount,
+            },
+        );
+
+        let cooling_off_deposits: Vec<(u64, U128)> = self
+            .get_cooling_off_deposits_of(&payee)
+            .into_iter()
+            .map(|(ends_at, amount)| (ends_at, U128(amount)))
+            .collect();
+
+        // The entry is dropped with its storage fee so that a new deposit pays for it again
+        let is_exit = remaining_balance == 0 && self.storage_fees.contains_key(&payee);
+
+        match is_exit {
+            true => self.remove_deposit(&payee),
+            false => self.set_deposit(&payee, remaining_balance),
+        }
+        self.cooling_off_deposits.remove(&payee);
+        self.update_extension_vote(&payee, balance, remaining_balance);
+
+        let storage_fee = match is_exit {
+            true => self.take_storage_fee(&payee, initial_storage_usage),
+            false => None,
+        };
+
+        self.transfer_funds(&payee, payment + storage_fee.unwrap_or(0)).then(
+            Promise::new(env::current_account_id()).function_call(
+                "on_withdraw_callback".to_string(),
+                json!({"payee": payee, "deposit": U128(payment), "amount": U128(payment), "storage_fee": storage_fee.map(U128), "cooling_off_deposits": cooling_off_deposits})
+                    .to_string()
+                    .into_bytes(),
+                0,
+                GAS_FOR_WITHDRAW_CALLBACK,
+            ),
+        );
+
+        if penalty > 0 {
+            match self.is_early_exit_penalty_to_creator {
+                true => {
+                    self.transfer_payout(&self.creator_account_id, penalty);
+                    self.total_funds -= penalty;
+                }
+                false => self.early_exit_penalties += penalty,
+            }
+        }
+
+        self.total_funds -= payment;
+        self.raised_funds -= amount;
+        self.unpaid_funding_amount = self.funding_amount_limit.saturating_sub(self.raised_funds);
+
+        // The hard cap is no longer reached, funding reopens until expires_at
+        if is_funded {
+            self.set_status(CampaignStatus::Funding);
+        }
+
+        log!(
+            "{} withdrawn {} NEAR tokens early with a penalty of {} — Total funds: {} — Unpaid funds: {}",
+            &payee,
+            payment,
+            penalty,
+            self.total_funds,
+            self.unpaid_funding_amount
+        );
+
+        EscrowEvent::Withdraw(WithdrawEvent {
+            account_id: payee,
+            amount: U128(payment),
+            balance: U128(remaining_balance),
+            total_funds: U128(self.total_funds),
+            unpaid_funding_amount: U128(self.unpaid_funding_amount),
+        })
+        .emit();
+    }
+
+    /// Pays the early exit penalties kept in the pot to the creator if the campaign refunds the
+    /// depositors instead of delegating
+    pub fn withdraw_early_exit_penalties(&mut self) {
+        self.update_status();
+
+        if env::predecessor_account_id() != self.creator_account_id {
+            env::panic_str("ERR_NOT_CREATOR");
+        }
+
+        if !self.is_withdrawal_allowed() || self.is_dao_created || self.early_exit_penalties == 0 {
+            env::panic_str("ERR_WITHDRAWAL_NOT_ALLOWED");
+        }
+
+        let amount = self.early_exit_penalties;
+
+        self.transfer_payout(&self.creator_account_id, amount);
+        self.early_exit_penalties = 0;
+        self.total_funds -= amount;
+
+        log!(
+            "{} withdrawn {} NEAR tokens of early exit penalties",
+            &self.creator_account_id,
+            amount
+        );
+    }
+
+    /// Adds the attached deposit to the match pool, only the sponsor may fund it during funding
+    #[payable]
+    pub fn fund_match(&mut self) {
+        self.update_status();
+
+        if Some(env::predecessor_account_id()) != self.sponsor_account_id {
+            env::panic_str("ERR_NOT_SPONSOR");
+        }
+
+        if !self.is_deposit_allowed() || env::attached_deposit() == 0 {
+            env::panic_str("ERR_DEPOSIT_NOT_ALLOWED");
+        }
+
+        self.match_pool += env::attached_deposit();
+
+        log!(
+            "{} funded the match pool with {} NEAR tokens — Match pool: {}",
+            &env::predecessor_account_id(),
+            env::attached_deposit(),
+            self.match_pool
+        );
+
+        EscrowEvent::MatchFunded(MatchFundedEvent {
+            sponsor_account_id: env::predecessor_account_id(),
+            amount: U128(env::attached_deposit()),
+            match_pool: U128(self.match_pool),
+        })
+        .emit();
+    }
+
+    /// Returns the unused match pool to the sponsor once funding ended. If the campaign refunds
+    /// the depositors, the funds matched to the DAO are returned too
+    pub fn withdraw_match(&mut self) {
+        self.update_status();
+
+        let sponsor_account_id = match &self.sponsor_account_id {
+            Some(account_id) if *account_id == env::predecessor_account_id() => account_id.clone(),
+            _ => env::panic_str("ERR_NOT_SPONSOR"),
+        };
+
+        let matched_funds = match !self.is_match_in_shares
+            && self.is_withdrawal_allowed()
+            && !self.is_dao_created
+        {
+            true => self.matched_funds,
+            false => 0,
+        };
+
+        let amount = self.match_pool + matched_funds;
+
+        if self.get_status() == CampaignStatus::Funding || amount == 0 {
+            env::panic_str("ERR_WITHDRAWAL_NOT_ALLOWED");
+        }
+
+        self.transfer_payout(&sponsor_account_id, amount);
+        self.match_pool = 0;
+        self.matched_funds -= matched_funds;
+        self.total_funds -= matched_funds;
+
+        log!(
+            "{} withdrawn {} NEAR tokens of the match pool",
+            &sponsor_account_id,
+            amount
+        );
+
+        EscrowEvent::MatchWithdrawn(MatchWithdrawnEvent {
+            sponsor_account_id,
+            amount: U128(amount),
+            matched_funds: U128(self.matched_funds),
+            total_funds: U128(self.total_funds),
+        })
+        .emit();
+    }
+
+    /// Pushes the refunds of up to limit depositors, starting at from_index or at the refund
+    /// cursor. Anyone may call it, already refunded depositors are skipped. Returns the index to
+    /// continue from
+    pub fn refund_batch(&mut self, from_index: Option<u64>, limit: u64) -> u64 {
+        self.update_status();
+
+        if !self.is_withdrawal_allowed() {
+            env::panic_str("ERR_WITHDRAWAL_NOT_ALLOWED");
+        }
+
+        let from_index = from_index.unwrap_or(self.refund_cursor);
+        let keys = self.deposits.keys_as_vector();
+        let to_index = std::cmp::min(from_index.saturating_add(limit), keys.len());
+        let accounts: Vec<AccountId> = (from_index..to_index)
+            .filter_map(|index| keys.get(index))
+            .collect();
+
+        for payee in accounts {
+            if self.is_refund_pending(&payee) {
+                self.internal_withdraw(payee);
+            }
+        }
+
+        // Progress is only recorded when the batch continues from the cursor
+        if from_index <= self.refund_cursor {
+            self.refund_cursor = std::cmp::max(self.refund_cursor, to_index);
+        }
+
+        to_index
+    }
+
+    /// Refunds the excess over the allocation of an oversubscribed campaign
+    #[payable]
+    pub fn withdraw_excess(&mut self) {
+        self.update_status();
+
+        if !self.is_excess_withdrawal_allowed() {
+            env::panic_str("ERR_WITHDRAWAL_NOT_ALLOWED");
+        }
+
+        let payee = env::predecessor_account_id();
+        let payment = self.get_excess_of(&payee);
+
+        if payment == 0 {
+            env::panic_str("ERR_NO_EXCESS_TO_WITHDRAW");
+        }
+
+        self.transfer_funds(&payee, payment).then(
+            Promise::new(env::current_account_id()).function_call(
+                "on_withdraw_excess_callback".to_string(),
+                json!({"payee": payee, "amount": U128(payment)})
+                    .to_string()
+                    .into_bytes(),
+                0,
+                GAS_FOR_WITHDRAW_CALLBACK,
+            ),
+        );
+
+        self.excess_withdrawals.insert(&payee, &payment);
+        self.total_funds = self.total_funds.wrapping_sub(payment);
+
+        log!(
+            "{} withdrawn {} excess NEAR tokens. Allocation {} — Total funds: {}",
+            &payee,
+            payment,
+            self.get_allocation_of(&payee),
+            self.total_funds
+        );
+
+        EscrowEvent::Withdraw(WithdrawEvent {
+            balance: U128(self.get_allocation_of(&payee)),
+            account_id: payee,
+            amount: U128(payment),
+            total_funds: U128(self.total_funds),
+            unpaid_funding_amount: U128(self.unpaid_funding_amount),
+        })
+        .emit();
+    }
+
+    /// Creates the DAO and the FT. After an unsuccessful delegation, only the failed
+    /// creations are retried, a new dao_name may be used if the DAO was not created.
+    /// Otherwise dao_name is ignored and the name of the created DAO is used.
+    #[payable]
+    pub fn delegate_funds(&mut self, dao_name: String) -> Promise {
+        self.update_status();
+
+        if !self.are_release_conditions_met() {
+            env::panic_str("ERR_RELEASE_CONDITIONS_NOT_MET");
+        }
+
+        if !self.is_delegation_allowed() {
+            env::panic_str("ERR_DELEGATE_NOT_ALLOWED");
+        }
+
+        // The FT is created under the name of the DAO
+        let dao_name = match self.is_dao_created {
+            true => self.dao_name.clone(),
+            false => dao_name,
+        };
+
+        self.set_status(CampaignStatus::Delegating);
+        self.delegation_started_at = env::block_timestamp();
+
+        // Reserved until on_delegate_callback, refunded into the pot if the DAO is not created
+        if !self.is_dao_created {
+            self.excess_dust = self.compute_excess_dust();
+            self.fee_amount = self.get_allocated_funds() * self.fee_basis_points as u128
+                / MAX_FEE_BASIS_POINTS as u128;
+            self.keeper_reward = self.get_allocated_funds()
+                * self.keeper_reward_basis_points as u128
+                / MAX_FEE_BASIS_POINTS as u128;
+        }
+
+        // The caller of the successful delegate_funds is rewarded
+        self.keeper_account_id = Some(env::predecessor_account_id());
+
+        // The creation deposits refunded to the caller after a failed delegation are reused
+        let attached_deposit = match self.funding_token_id {
+            Some(_) => {
+                env::attached_deposit()
+                    + self
+                        .keeper_refunds
+                        .remove(&env::predecessor_account_id())
+                        .unwrap_or(0)
+            }
+            None => env::attached_deposit(),
+        };
+
+        let ft_amount = match self.is_ft_created {
+            true => 0,
+            false => FT_ATTACHED_DEPOSIT,
+        };
+
+        // When funding with a token, the DAO and FT creation deposits are attached by the caller
+        let mut dao_amount = match (&self.funding_token_id, self.is_dao_created) {
+            (_, true) => 0,
+            (Some(_), false) => match attached_deposit.checked_sub(ft_amount) {
+                Some(amount) if amount > 0 => amount,
+                _ => env::panic_str("ERR_INSUFFICIENT_ATTACHED_DEPOSIT"),
+            },
+            // The FT creation deposit is deducted even if the FT was already created
+            (None, false) => match self
+                .get_allocated_funds()
+                .checked_sub(FT_ATTACHED_DEPOSIT)
+                .and_then(|amount| amount.checked_sub(self.fee_amount))
+                .and_then(|amount| amount.checked_sub(self.keeper_reward))
+                .map(|amount| amount + self.get_pot_funds())
+            {
+                // Later tranches are held by the escrow until their milestone is approved
+                Some(amount) => self.get_tranche_amount(amount, 0),
+                None => env::panic_str("ERR_TOTAL_FUNDS_OVERFLOW"),
+            },
+        };
+
+        if self.funding_token_id.is_some() && attached_deposit < dao_amount + ft_amount {
+            env::panic_str("ERR_INSUFFICIENT_ATTACHED_DEPOSIT");
+        }
+
+        // The factories refund the deposits of an unsuccessful creation to this contract
+        if env::account_balance() < dao_amount + ft_amount {
+            env::panic_str("ERR_DELEGATION_REFUNDS_PENDING");
+        }
+
+        // Only the balance above the storage reserve is forwarded, the DAO gets the rest. The funds
+        // owed to others do not cover the reserve
+        let storage_shortfall = self.get_storage_reserve().saturating_sub(
+            env::account_balance()
+                .saturating_sub(dao_amount + ft_amount + self.get_held_funds(dao_amount)),
+        );
+
+        if storage_shortfall > 0 {
+            dao_amount = match dao_amount.checked_sub(storage_shortfall) {
+                Some(amount) if amount > 0 => amount,
+                _ => env::panic_str("ERR_STORAGE_RESERVE_NOT_COVERED"),
+            };
+        }
+
+        if self.funding_token_id.is_some() {
+            self.keeper_deposit = dao_amount;
+        }
+
+        let mut promise: Option<Promise> = None;
+
+        if !self.is_dao_created {
+            promise = Some(
+                Promise::new(self.dao_factory_account_id.clone()).function_call(
+                    "create_dao".to_string(),
+                    json!({"dao_name": dao_name.clone(), "deposits": self.get_deposit_accounts() })
+                        .to_string()
+                        .into_bytes(),
+                    dao_amount,
+                    GAS_FOR_CREATE_DAO,
+                ),
+            );
+        }
+
+        if !self.is_ft_created {
+            let ft_promise = Promise::new(self.ft_factory_account_id.clone()).function_call(
+                "create_ft".to_string(),
+                // The creator gets the rounding dust of the token claims. The treasury is set on
+                // the FT init, while the DAO account is named by the dao-factory once it creates
+                // the DAO in this same batch, and only known here through get_dao_by_escrow_account
+                json!({"name": dao_name.clone(), "treasury_account_id": self.creator_account_id})
+                    .to_string()
+                    .into_bytes(),
+                ft_amount,
+                GAS_FOR_CREATE_FT,
+            );
+
+            promise = Some(match promise {
+                Some(dao_promise) => dao_promise.and(ft_promise),
+                None => ft_promise,
+            });
+        }
+
+        let callback = Promise::new(env::current_account_id()).function_call(
+            "on_delegate_callback".to_string(),
+            json!({"dao_name": dao_name.clone()})
+                .to_string()
+                .into_bytes(),
+            0,
+            self.get_delegate_callback_gas(),
+        );
+
+        EscrowEvent::DelegateStarted(DelegateStartedEvent {
+            dao_name,
+            dao_amount: U128(dao_amount),
+            ft_amount: U128(ft_amount),
+            fee_amount: U128(self.fee_amount),
+            depositors_count: self.get_depositors_count(),
+        })
+        .emit();
+
+        promise.unwrap().then(callback)
+    }
+
+    /// Records the outcome of the DAO and FT creations, results are in the order they were
+    /// requested by delegate_funds
+    #[private]
+    pub fn on_delegate_callback(&mut self, dao_name: String) -> bool {
+        if self.status != CampaignStatus::Delegating {
+            env::panic_str("ERR_DELEGATE_NOT_IN_PROGRESS");
+        }
+
+        let expected_results_count = !self.is_dao_created as u64 + !self.is_ft_created as u64;
+
+        if env::promise_results_count() != expected_results_count {
+            env::panic_str("ERR_CALLBACK_METHOD");
+        }
+
+        let mut results = (0..expected_results_count).map(env::promise_result);
+
+        // The factories refund the creation deposits attached by the keeper if they fail
+        let mut keeper_refund: Balance = 0;
+
+        // Create DAO Contract
+        if !self.is_dao_created {
+            let on_create_dao_successful = Self::is_promise_result_true(&results.next().unwrap());
+
+            if on_create_dao_successful {
+                let dao_funds = self.get_dao_funds();
+                self.undisbursed_funds = dao_funds - self.get_tranche_amount(dao_funds, 0);
+
+                if !self.tranche_basis_points.is_empty() {
+                    self.released_tranches = 1;
+                }
+
+                if self.funding_token_id.is_some() {
+                    self.pending_dao_funds = dao_funds - self.undisbursed_funds;
+                }
+
+                // The excess that is yet to be withdrawn and the undisbursed tranches stay in the
+                // escrow
+                self.total_funds -=
+                    self.get_allocated_funds() + self.get_pot_funds() - self.undisbursed_funds;
+                self.dao_name = dao_name.clone();
+                self.is_dao_created = true;
+            } else {
+                // The fee and the keeper reward are refunded into the pot, the excess dust to the
+                // excess of the depositors
+                self.fee_amount = 0;
+                self.keeper_reward = 0;
+                self.excess_dust = 0;
+                keeper_refund += self.keeper_deposit;
+            }
+
+            self.keeper_deposit = 0;
+        }
+
+        // Create FT Contract
+        if !self.is_ft_created {
+            let on_create_ft_successful = Self::is_promise_result_true(&results.next().unwrap());
+
+            if on_create_ft_successful {
+                self.is_ft_created = true;
+
+                // Lost by the depositors if the DAO is never created and refunds open
+                if self.funding_token_id.is_none() && !self.is_dao_created {
+                    self.delegation_costs = FT_ATTACHED_DEPOSIT;
+                }
+            } else if self.funding_token_id.is_some() {
+                keeper_refund += FT_ATTACHED_DEPOSIT;
+            }
+        }
+
+        if keeper_refund > 0 {
+            let keeper_account_id = self.keeper_account_id.clone().unwrap();
+            let refund = self.get_keeper_refund_of(&keeper_account_id) + keeper_refund;
+            self.keeper_refunds.insert(&keeper_account_id, &refund);
+        }
+
+        if self.is_dao_created && self.is_ft_created {
+            // The fee is only charged once the delegation succeeds
+            if self.fee_amount > 0 {
+                let fee_account_id = self.fee_account_id.clone().unwrap();
+                self.transfer_payout(&fee_account_id, self.fee_amount);
+            }
+
+            if self.keeper_reward > 0 {
+                let keeper_account_id = self.keeper_account_id.clone().unwrap();
+                self.transfer_payout(&keeper_account_id, self.keeper_reward);
+
+                log!(
+                    "{} rewarded with {} tokens for the delegation",
+                    &keeper_account_id,
+                    self.keeper_reward
+                );
+            }
+
+            self.delegation_costs = 0;
+            self.set_status(CampaignStatus::Delegated);
+            EscrowEvent::DelegateSucceeded(DelegateSucceededEvent { dao_name }).emit();
+        } else {
+            self.delegation_failed_at = env::block_timestamp();
+            self.set_status(CampaignStatus::DelegationFailed);
+            EscrowEvent::DelegateFailed(DelegateFailedEvent {
+                dao_name,
+                is_dao_created: self.is_dao_created,
+                is_ft_created: self.is_ft_created,
+            })
+            .emit();
+        }
+
+        if self.pending_dao_funds > 0 {
+            self.forward_funds();
+        }
+
+        self.is_dao_created && self.is_ft_created
+    }
+
+    /// Restores the payee balance and the totals if the transfer of a withdrawal failed, along with
+    /// the storage fee refunded with it and the deposits of an early withdrawal still in their
+    /// cooling-off period
+    #[private]
+    pub fn on_withdraw_callback(
+        &mut self,
+        payee: AccountId,
+        deposit: U128,
+        amount: U128,
+        bonus: Option<U128>,
+        storage_fee: Option<U128>,
+        cooling_off_deposits: Option<Vec<(u64, U128)>>,
+    ) -> bool {
+        if near_sdk::is_promise_success() {
+            log!("{} withdrawal of {} tokens succeeded", &payee, amount.0);
+
+            return true;
+        }
+
+        // The deposit also includes the excess withdrawn before
+        let held_funds = deposit.0 - self.excess_withdrawals.get(&payee).unwrap_or(0);
+
+        // Early withdrawals happen during funding, payee may have deposited again since
+        let current_balance = self.deposits_of(&payee);
+        let balance = current_balance + deposit.0;
+
+        self.set_deposit(&payee, balance);
+        self.update_extension_vote(&payee, current_balance, balance);
+
+        if let Some(bonus) = bonus.filter(|bonus| bonus.0 > 0) {
+            self.bonus_weights
+                .insert(&payee, &(self.get_bonus_weight_of(&payee) + bonus.0));
+            self.total_bonus_weight += bonus.0;
+        }
+
+        if let Some(storage_fee) = storage_fee {
+            match self.storage_fees.get(&payee) {
+                None => {
+                    self.storage_fees.insert(&payee, &storage_fee.0);
+                    self.total_storage_fees += storage_fee.0;
+                }
+                // A new deposit paid the storage fee again, the returned one stays claimable
+                Some(_) => {
+                    self.pending_payouts.insert(
+                        &payee,
+                        &(self.get_pending_payout_of(&payee) + storage_fee.0),
+                    );
+                }
+            }
+        }
+
+        if let Some(cooling_off_deposits) = cooling_off_deposits {
+            let mut restored_deposits: Vec<(u64, Balance)> = cooling_off_deposits
+                .into_iter()
+                .filter(|(ends_at, _)| env::block_timestamp() <= *ends_at)
+                .map(|(ends_at, amount)| (ends_at, amount.0))
+                .collect();
+
+            if !restored_deposits.is_empty() {
+                restored_deposits.extend(self.get_cooling_off_deposits_of(&payee));
+                self.cooling_off_deposits.insert(&payee, &restored_deposits);
+            }
+        }
+
+        self.delegation_costs = self.delegation_costs.wrapping_add(held_funds - amount.0);
+        self.total_funds = self.total_funds.wrapping_add(held_funds);
+        self.raised_funds = self.raised_funds.wrapping_add(deposit.0);
+        self.unpaid_funding_amount = self.funding_amount_limit.saturating_sub(self.raised_funds);
+
+        // A restored early withdrawal may reach the hard cap again
+        if self.status == CampaignStatus::Funding
+            && !self.is_oversubscription_allowed
+            && self.is_funding_reached()
+        {
+            self.set_status(CampaignStatus::Funded);
+        }
+
+        log!(
+            "{} withdrawal of {} tokens failed. Balance restored to {}",
+            &payee,
+            amount.0,
+            balance
+        );
+
+        EscrowEvent::WithdrawFailed(WithdrawFailedEvent {
+            account_id: payee,
+            amount,
+            balance: U128(balance),
+            total_funds: U128(self.total_funds),
+            unpaid_funding_amount: U128(self.unpaid_funding_amount),
+        })
+        .emit();
+
+        false
+    }
+
+    /// Allows the excess to be withdrawn again if the transfer failed
+    #[private]
+    pub fn on_withdraw_excess_callback(&mut self, payee: AccountId, amount: U128) -> bool {
+        if near_sdk::is_promise_success() {
+            log!(
+                "{} excess withdrawal of {} tokens succeeded",
+                &payee,
+                amount.0
+            );
+
+            return true;
+        }
+
+        self.excess_withdrawals.remove(&payee);
+        self.total_funds = self.total_funds.wrapping_add(amount.0);
+
+        log!("{} excess withdrawal of {} tokens failed", &payee, amount.0);
+
+        EscrowEvent::WithdrawFailed(WithdrawFailedEvent {
+            balance: U128(self.deposits_of(&payee)),
+            account_id: payee,
+            amount,
+            total_funds: U128(self.total_funds),
+            unpaid_funding_amount: U128(self.unpaid_funding_amount),
+        })
+        .emit();
+
+        false
+    }
+
+    /// Keeps the payout claimable with withdraw_payout if its transfer failed
+    #[private]
+    pub fn on_payout_callback(&mut self, account_id: AccountId, amount: U128) -> bool {
+        if near_sdk::is_promise_success() {
+            log!("{} payout of {} tokens succeeded", &account_id, amount.0);
+
+            return true;
+        }
+
+        self.pending_payouts.insert(
+            &account_id,
+            &(self.get_pending_payout_of(&account_id) + amount.0),
+        );
+
+        log!("{} payout of {} tokens failed", &account_id, amount.0);
+
+        false
+    }
+
+    /// Retries the transfer of the payouts of the caller that failed
+    pub fn withdraw_payout(&mut self) -> Promise {
+        let account_id = env::predecessor_account_id();
+
+        let amount = match self.pending_payouts.remove(&account_id) {
+            Some(amount) => amount,
+            None => env::panic_str("ERR_NO_PENDING_PAYOUT"),
+        };
+
+        log!(
+            "{} withdrawn {} tokens of failed payouts",
+            &account_id,
+            amount
+        );
+
+        self.transfer_payout(&account_id, amount)
+    }
+
+    /// Returns the creation deposits the factories refunded to the caller after failed
+    /// delegations, they are otherwise reused by the next delegate_funds of the caller
+    pub fn withdraw_keeper_refund(&mut self) -> Promise {
+        let keeper_account_id = env::predecessor_account_id();
+        let amount = self.get_keeper_refund_of(&keeper_account_id);
+
+        if self.status == CampaignStatus::Delegating || amount == 0 {
+            env::panic_str("ERR_WITHDRAWAL_NOT_ALLOWED");
+        }
+
+        // The factories refund the deposits of an unsuccessful creation to this contract
+        if env::account_balance() < amount {
+            env::panic_str("ERR_DELEGATION_REFUNDS_PENDING");
+        }
+
+        self.keeper_refunds.remove(&keeper_account_id);
+
+        log!(
+            "{} withdrawn {} NEAR tokens of failed creation deposits",
+            &keeper_account_id,
+            amount
+        );
+
+        Promise::new(keeper_account_id.clone())
+            .transfer(amount)
+            .then(
+                Promise::new(env::current_account_id()).function_call(
+                    "on_keeper_refund_callback".to_string(),
+                    json!({"keeper_account_id": keeper_account_id, "amount": U128(amount)})
+                        .to_string()
+                        .into_bytes(),
+                    0,
+                    GAS_FOR_PAYOUT_CALLBACK,
+                ),
+            )
+    }
+
+    /// Allows the keeper refund to be withdrawn again if its transfer failed
+    #[private]
+    pub fn on_keeper_refund_callback(
+        &mut self,
+        keeper_account_id: AccountId,
+        amount: U128,
+    ) -> bool {
+        if near_sdk::is_promise_success() {
+            return true;
+        }
+
+        let refund = self.get_keeper_refund_of(&keeper_account_id) + amount.0;
+        self.keeper_refunds.insert(&keeper_account_id, &refund);
+
+        log!(
+            "{} keeper refund of {} NEAR tokens failed",
+            &keeper_account_id,
+            amount.0
+        );
+
+        false
+    }
+
+    /// Transfers the funds held by the escrow for the created DAO
+    pub fn forward_funds(&mut self) -> Promise {
+        if !self.is_dao_created || self.pending_dao_funds == 0 {
+            env::panic_str("ERR_FORWARD_FUNDS_NOT_ALLOWED");
+        }
+
+        let amount = self.pending_dao_funds;
+        self.pending_dao_funds = 0;
+
+        Promise::new(self.dao_factory_account_id.clone())
+            .function_call(
+                "get_dao_by_escrow_account".to_string(),
+                json!({"account": env::current_account_id()})
+                    .to_string()
+                    .into_bytes(),
+                0,
+                GAS_FOR_GET_DAO_ACCOUNT,
+            )
+            .then(Promise::new(env::current_account_id()).function_call(
+                "on_get_dao_account_callback".to_string(),
+                json!({ "amount": U128(amount) }).to_string().into_bytes(),
+                0,
+                GAS_FOR_FT_TRANSFER + GAS_FOR_FT_TRANSFER_CALLBACK * 2,
+            ))
+    }
+
+    #[private]
+    pub fn on_get_dao_account_callback(&mut self, amount: U128) -> PromiseOrValue<bool> {
+        let dao_account_id = match env::promise_result(0) {
+            PromiseResult::Successful(result) => {
+                near_sdk::serde_json::from_slice::<String>(&result)
+                    .ok()
+                    .and_then(|account_id| account_id.parse::<AccountId>().ok())
+            }
+            _ => None,
+        };
+
+        match dao_account_id {
+            Some(dao_account_id) => PromiseOrValue::Promise(
+                self.transfer_funds(&dao_account_id, amount.0).then(
+                    Promise::new(env::current_account_id()).function_call(
+                        "on_forward_funds_callback".to_string(),
+                        json!({"dao_account_id": dao_account_id, "amount": amount})
+                            .to_string()
+                            .into_bytes(),
+                        0,
+                        GAS_FOR_FT_TRANSFER_CALLBACK,
+                    ),
+                ),
+            ),
+            None => {
+                self.pending_dao_funds = self.pending_dao_funds.wrapping_add(amount.0);
+                log!("ERR_DAO_ACCOUNT_NOT_FOUND");
+                PromiseOrValue::Value(false)
+            }
+        }
+    }
+
+    #[private]
+    pub fn on_forward_funds_callback(&mut self, dao_account_id: AccountId, amount: U128) -> bool {
+        if near_sdk::is_promise_success() {
+            log!("{} tokens transferred to {}", amount.0, dao_account_id);
+            return true;
+        }
+
+        self.pending_dao_funds = self.pending_dao_funds.wrapping_add(amount.0);
+        log!(
+            "Transfer of {} tokens to {} failed",
+            amount.0,
+            dao_account_id
+        );
+
+        false
+    }
+
+    /// Votes on the pending milestone. The milestone_verifier_account_id decides alone, otherwise
+    /// depositors vote with their allocations and a majority of the allocated funds decides.
+    /// Approving it releases the next tranche to the DAO, rejecting it opens the refunds of the
+    /// undisbursed tranches
+    pub fn vote_milestone(&mut self, approve: bool) {
+        if !self.is_milestone_pending() {
+            env::panic_str("ERR_NO_PENDING_MILESTONE");
+        }
+
+        let account_id = env::predecessor_account_id();
+        let milestone = self.released_tranches;
+
+        let weight = match &self.milestone_verifier_account_id {
+            Some(verifier_account_id) if *verifier_account_id == account_id => {
+                self.get_allocated_funds()
+            }
+            Some(_) => env::panic_str("ERR_NOT_MILESTONE_VERIFIER"),
+            None => self.get_allocation_of(&account_id),
+        };
+
+        if weight == 0 {
+            env::panic_str("ERR_ZERO_BALANCE");
+        }
+
+        // Replaces any previous vote of the account
+        match self.get_milestone_vote_of(account_id.clone()) {
+            Some(true) => self.milestone_votes_for -= weight,
+            Some(false) => self.milestone_votes_against -= weight,
+            None => {}
+        }
+
+        match approve {
+            true => self.milestone_votes_for += weight,
+            false => self.milestone_votes_against += weight,
+        }
+
+        self.milestone_votes
+            .insert(&account_id, &(milestone, approve));
+
+        EscrowEvent::MilestoneVoted(MilestoneVotedEvent {
+            milestone,
+            account_id,
+            approve,
+            weight: U128(weight),
+            votes_for: U128(self.milestone_votes_for),
+            votes_against: U128(self.milestone_votes_against),
+        })
+        .emit();
+
+        let majority = self.get_allocated_funds() / 2;
+
+        if self.milestone_votes_for > majority {
+            self.release_tranche();
+        } else if self.milestone_votes_against > majority {
+            self.set_status(CampaignStatus::MilestoneRejected);
+
+            log!(
+                "Milestone {} rejected. Undisbursed funds: {}",
+                milestone,
+                self.undisbursed_funds
+            );
+
+            EscrowEvent::MilestoneRejected(MilestoneRejectedEvent {
+                milestone,
+                undisbursed_funds: U128(self.undisbursed_funds),
+            })
+            .emit();
+        }
+    }
+
+    /// Allows the refund of the undisbursed tranches to be withdrawn again if the transfer failed
+    #[private]
+    pub fn on_tranche_refund_callback(&mut self, payee: AccountId, amount: U128) -> bool {
+        if near_sdk::is_promise_success() {
+            log!("{} tranche refund of {} tokens succeeded", &payee, amount.0);
+
+            return true;
+        }
+
+        self.tranche_refunds.remove(&payee);
+        self.tranche_refunded_allocations -= self.get_allocation_of(&payee);
+        self.tranche_refunded_funds -= amount.0;
+        self.total_funds = self.total_funds.wrapping_add(amount.0);
+
+        log!("{} tranche refund of {} tokens failed", &payee, amount.0);
+
+        EscrowEvent::WithdrawFailed(WithdrawFailedEvent {
+            balance: U128(self.deposits_of(&payee)),
+            account_id: payee,
+            amount,
+            total_funds: U128(self.total_funds),
+            unpaid_funding_amount: U128(self.unpaid_funding_amount),
+        })
+        .emit();
+
+        false
+    }
+
+    /// Refunds the balance of payee, minus its share of the delegation costs
+    fn internal_withdraw(&mut self, payee: AccountId) {
+        if self.status == CampaignStatus::MilestoneRejected {
+            return self.internal_withdraw_tranches(payee);
+        }
+
+        let deposit = self.deposits_of(&payee);
+        let excess_withdrawal = self.excess_withdrawals.get(&payee).unwrap_or(0);
+        let held_funds = deposit.saturating_sub(excess_withdrawal);
+        let payment = self.get_refund_amount(held_funds);
+
+        // The zeroed entry is kept so that the indexes of refund_batch do not shift
+        let initial_storage_usage = env::storage_usage();
+        let bonus = self.take_bonus_weight(&payee);
+        self.set_deposit(&payee, 0);
+        let storage_fee = self.take_storage_fee(&payee, initial_storage_usage);
+
+        self.transfer_funds(&payee, payment + storage_fee.unwrap_or(0)).then(
+            Promise::new(env::current_account_id()).function_call(
+                "on_withdraw_callback".to_string(),
+                json!({"payee": payee, "deposit": U128(deposit), "amount": U128(payment), "bonus": U128(bonus), "storage_fee": storage_fee.map(U128)})
+                    .to_string()
+                    .into_bytes(),
+                0,
+                GAS_FOR_WITHDRAW_CALLBACK,
+            ),
+        );
+
+        self.delegation_costs = self.delegation_costs.wrapping_sub(held_funds - payment);
+        self.total_funds = self.total_funds.wrapping_sub(held_funds);
+        self.raised_funds = self.raised_funds.wrapping_sub(deposit);
+        self.unpaid_funding_amount = self.funding_amount_limit.saturating_sub(self.raised_funds);
+
+        log!(
+            "{} withdrawn {} NEAR tokens. New balance {} — Total funds: {} — Unpaid funds: {}",
+            &payee,
+            payment,
+            self.deposits_of(&payee),
+            self.total_funds,
+            self.unpaid_funding_amount
+        );
+
+        EscrowEvent::Withdraw(WithdrawEvent {
+            balance: U128(self.deposits_of(&payee)),
+            account_id: payee,
+            amount: U128(payment),
+            total_funds: U128(self.total_funds),
+            unpaid_funding_amount: U128(self.unpaid_funding_amount),
+        })
+        .emit();
+    }
+
+    /// Refunds the share of payee of the tranches undisbursed when a milestone was rejected, the
+    /// last refund takes the rounding dust. The deposit is kept, it still accounts for the shares
+    /// of the FT
+    fn internal_withdraw_tranches(&mut self, payee: AccountId) {
+        let allocation = self.get_allocation_of(&payee);
+        let refunded_allocations = self.tranche_refunded_allocations + allocation;
+
+        let payment = match refunded_allocations == self.get_allocated_funds() {
+            true => self.undisbursed_funds - self.tranche_refunded_funds,
+            false => (U256::from(self.undisbursed_funds) * U256::from(allocation)
+                / U256::from(self.get_allocated_funds()))
+            .as_u128(),
+        };
+
+        self.transfer_funds(&payee, payment).then(
+            Promise::new(env::current_account_id()).function_call(
+                "on_tranche_refund_callback".to_string(),
+                json!({"payee": payee, "amount": U128(payment)})
+                    .to_string()
+                    .into_bytes(),
+                0,
+                GAS_FOR_WITHDRAW_CALLBACK,
+            ),
+        );
+
+        self.tranche_refunds.insert(&payee, &payment);
+        self.tranche_refunded_allocations = refunded_allocations;
+        self.tranche_refunded_funds += payment;
+        self.total_funds = self.total_funds.wrapping_sub(payment);
+
+        log!(
+            "{} withdrawn {} NEAR tokens of the undisbursed tranches — Total funds: {}",
+            &payee,
+            payment,
+            self.total_funds
+        );
+
+        EscrowEvent::Withdraw(WithdrawEvent {
+            balance: U128(self.deposits_of(&payee)),
+            account_id: payee,
+            amount: U128(payment),
+            total_funds: U128(self.total_funds),
+            unpaid_funding_amount: U128(self.unpaid_funding_amount),
+        })
+        .emit();
+    }
+
+    /// Releases the tranche of the approved milestone to the DAO, the last tranche takes the
+    /// rounding dust
+    fn release_tranche(&mut self) {
+        let milestone = self.released_tranches;
+
+        let amount = match milestone as usize + 1 == self.tranche_basis_points.len() {
+            true => self.undisbursed_funds,
+            false => self.get_tranche_amount(self.get_dao_funds(), milestone as usize),
+        };
+
+        self.undisbursed_funds -= amount;
+        self.total_funds -= amount;
+        self.pending_dao_funds += amount;
+        self.released_tranches += 1;
+        self.milestone_votes_for = 0;
+        self.milestone_votes_against = 0;
+
+        log!(
+            "Milestone {} approved. Released {} tokens",
+            milestone,
+            amount
+        );
+
+        EscrowEvent::MilestoneApproved(MilestoneApprovedEvent {
+            milestone,
+            amount: U128(amount),
+        })
+        .emit();
+
+        self.forward_funds();
+    }
+
+    /// Allocated funds and early exit penalties, minus the fee, the keeper reward and, when funding
+    /// in NEAR, the FT creation deposit
+    fn get_dao_funds(&self) -> Balance {
+        let ft_amount = match self.funding_token_id {
+            Some(_) => 0,
+            None => FT_ATTACHED_DEPOSIT,
+        };
+
+        self.get_allocated_funds()
+            .saturating_sub(ft_amount)
+            .saturating_sub(self.fee_amount)
+            .saturating_sub(self.keeper_reward)
+            .saturating_add(self.get_pot_funds())
+    }
+
+    /// Funds that are not allocations and join the DAO funds: the early exit penalties kept in the
+    /// pot, the funds matched outside of the shares and the excess dust
+    fn get_pot_funds(&self) -> Balance {
+        match self.is_match_in_shares {
+            true => self.early_exit_penalties + self.excess_dust,
+            false => self.early_exit_penalties + self.matched_funds + self.excess_dust,
+        }
+    }
+
+    /// NEAR held by the escrow for others while delegating dao_amount: the fee and the keeper
+    /// reward, the match pool, the excess yet to be withdrawn and the undisbursed tranches
+    fn get_held_funds(&self, dao_amount: Balance) -> Balance {
+        if self.funding_token_id.is_some() {
+            return 0;
+        }
+
+        let (excess, undisbursed_funds) = match self.is_dao_created {
+            true => (
+                self.total_funds.saturating_sub(self.undisbursed_funds),
+                self.undisbursed_funds,
+            ),
+            false => (
+                self.total_funds
+                    .saturating_sub(self.get_allocated_funds() + self.get_pot_funds()),
+                self.get_dao_funds().saturating_sub(dao_amount),
+            ),
+        };
+
+        self.fee_amount + self.keeper_reward + self.match_pool + excess + undisbursed_funds
+    }
+
+    /// Share of dao_funds released by the tranche at index, all of it without tranches
+    fn get_tranche_amount(&self, dao_funds: Balance, index: usize) -> Balance {
+        match self.tranche_basis_points.get(index) {
+            Some(basis_points) => (U256::from(dao_funds) * U256::from(*basis_points)
+                / U256::from(MAX_TRANCHE_BASIS_POINTS))
+            .as_u128(),
+            None => dao_funds,
+        }
+    }
+
+    /// Refunds of a rejected milestone are paid once per allocation, other refunds once per deposit
+    fn is_refund_pending(&self, payee: &AccountId) -> bool {
+        match self.status {
+            CampaignStatus::MilestoneRejected => {
+                self.get_allocation_of(payee) > 0 && !self.tranche_refunds.contains_key(payee)
+            }
+            _ => self.deposits_of(payee) > 0,
+        }
+    }
+
+    /// Queries the attestation registry, on_verify_deposit_callback records or refunds the deposit
+    fn verify_deposit(
+        &mut self,
+        payee: AccountId,
+        amount: Balance,
+        sender_id: AccountId,
+    ) -> Promise {
+        self.update_status();
+
+        let is_new_depositor = self.deposits.get(&payee).is_none();
+
+        if let Err(err) = self.prepare_deposit(&payee, amount) {
+            env::panic_str(err);
+        }
+
+        // Only checked here, the callback records the deposit and charges its storage fee
+        if is_new_depositor {
+            self.release_storage(&payee);
+        }
+
+        Promise::new(self.attestation_registry_id.clone().unwrap())
+            .function_call(
+                "is_verified".to_string(),
+                json!({ "account_id": payee }).to_string().into_bytes(),
+                0,
+                GAS_FOR_VERIFY_ACCOUNT,
+            )
+            .then(
+                Promise::new(env::current_account_id()).function_call(
+                    "on_verify_deposit_callback".to_string(),
+                    json!({"payee": payee, "amount": U128(amount), "sender_id": sender_id})
+                        .to_string()
+                        .into_bytes(),
+                    0,
+                    GAS_FOR_VERIFY_CALLBACK,
+                ),
+            )
+    }
+
+    /// Checks that payee may deposit amount, returns the surplus over the unpaid funding amount
+    fn check_deposit(&self, payee: &AccountId, amount: Balance) -> Result<Balance, &'static str> {
+        if env::current_account_id() == *payee {
+            return Err("ERR_OWNER_SHOULD_NOT_DEPOSIT");
+        }
+
+        if amount == 0 {
+            return Err("ERR_DEPOSIT_SHOULD_NOT_BE_0");
+        }
+
+        if !self.is_deposit_allowed() {
+            return Err("ERR_DEPOSIT_NOT_ALLOWED");
+        }
+
+        if self.is_allowlist_enabled && !self.allowlist.contains(payee) {
+            return Err("ERR_NOT_ALLOWLISTED");
+        }
+
+        let surplus = match self.is_oversubscription_allowed {
+            true => 0,
+            false => amount.saturating_sub(self.get_unpaid_funding_amount()),
+        };
+
+        let amount = amount - surplus;
+        let new_balance = self.deposits_of(payee).wrapping_add(amount);
+
+        // A deposit that completes the funding may be below the minimum
+        let is_funding_completed =
+            !self.is_oversubscription_allowed && amount == self.get_unpaid_funding_amount();
+
+        if new_balance < self.min_deposit && !is_funding_completed {
+            return Err("ERR_MIN_DEPOSIT_NOT_REACHED");
+        }
+
+        if let Some(max_deposit) = self.max_deposit_per_account {
+            if new_balance > max_deposit {
+                return Err("ERR_MAX_DEPOSIT_PER_ACCOUNT_EXCEEDED");
+            }
+        }
+
+        Ok(surplus)
+    }
+
+    fn add_bonus_weight(&mut self, payee: &AccountId, amount: Balance) {
+        let bonus = amount * Balance::from(self.get_current_bonus_basis_points())
+            / Balance::from(MAX_EARLY_BONUS_BASIS_POINTS);
+
+        if bonus > 0 {
+            self.bonus_weights
+                .insert(payee, &(self.get_bonus_weight_of(payee) + bonus));
+            self.total_bonus_weight += bonus;
+        }
+    }
+
+    /// Removes the share of the bonus of payee of amount out of its balance
+    fn forfeit_bonus_weight(&mut self, payee: &AccountId, amount: Balance, balance: Balance) {
+        let bonus = self.get_bonus_weight_of(payee);
+        let forfeited_bonus =
+            (U256::from(bonus) * U256::from(amount) / U256::from(balance)).as_u128();
+
+        match bonus - forfeited_bonus {
+            0 => self.bonus_weights.remove(payee),
+            bonus => self.bonus_weights.insert(payee, &bonus),
+        };
+        self.total_bonus_weight -= forfeited_bonus;
+    }
+
+    /// Removes the bonus of payee and returns it
+    fn take_bonus_weight(&mut self, payee: &AccountId) -> Balance {
+        let bonus = self.bonus_weights.remove(payee).unwrap_or(0);
+        self.total_bonus_weight -= bonus;
+
+        bonus
+    }
+
+    /// Matches the deposit of payee 1:1 from the match pool
+    fn match_deposit(&mut self, payee: &AccountId, amount: Balance) {
+        let sponsor_account_id = match &self.sponsor_account_id {
+            Some(account_id) if account_id != payee => account_id.clone(),
+            _ => return,
+        };
+
+        let matched = match self.is_match_in_shares && !self.is_oversubscription_allowed {
+            true => amount.min(self.match_pool).min(self.unpaid_funding_amount),
+            false => amount.min(self.match_pool),
+        };
+
+        if matched == 0 {
+            return;
+        }
+
+        if self.is_match_in_shares {
+            let sponsor_balance = self.deposits_of(&sponsor_account_id);
+
+            self.set_deposit(&sponsor_account_id, sponsor_balance + matched);
+            self.update_extension_vote(
+                &sponsor_account_id,
+                sponsor_balance,
+                sponsor_balance + matched,
+            );
+            self.raised_funds += matched;
+            self.unpaid_funding_amount =
+                self.funding_amount_limit.saturating_sub(self.raised_funds);
+        }
+
+        self.matches
+            .insert(payee, &(self.get_match_of(payee) + matched));
+        self.match_pool -= matched;
+        self.matched_funds += matched;
+        self.total_funds += matched;
+
+        log!(
+            "{} matched {} NEAR tokens of the deposit of {} — Match pool: {}",
+            &sponsor_account_id,
+            matched,
+            payee,
+            self.match_pool
+        );
+
+        EscrowEvent::DepositMatched(DepositMatchedEvent {
+            sponsor_account_id,
+            account_id: payee.clone(),
+            amount: U128(matched),
+            match_pool: U128(self.match_pool),
+            total_funds: U128(self.total_funds),
+        })
+        .emit();
+    }
+
+    fn is_sponsor_position(&self, account_id: &AccountId) -> bool {
+        self.is_match_in_shares && self.sponsor_account_id.as_ref() == Some(account_id)
+    }
+
+    /// Returns the match of up to amount of the deposits of payee to the match pool
+    fn unmatch_deposit(&mut self, payee: &AccountId, amount: Balance) {
+        let match_amount = self.get_match_of(payee);
+        let matched = match_amount.min(amount);
+
+        if matched == 0 {
+            return;
+        }
+
+        match match_amount - matched {
+            0 => self.matches.remove(payee),
+            match_amount => self.matches.insert(payee, &match_amount),
+        };
+
+        if self.is_match_in_shares {
+            let sponsor_account_id = self.sponsor_account_id.clone().unwrap();
+            let sponsor_balance = self.deposits_of(&sponsor_account_id);
+
+            self.set_deposit(&sponsor_account_id, sponsor_balance - matched);
+            self.update_extension_vote(
+                &sponsor_account_id,
+                sponsor_balance,
+                sponsor_balance - matched,
+            );
+            self.raised_funds -= matched;
+            self.unpaid_funding_amount =
+                self.funding_amount_limit.saturating_sub(self.raised_funds);
+        }
+
+        self.match_pool += matched;
+        self.matched_funds -= matched;
+        self.total_funds -= matched;
+    }
+
+    /// Writes the balance of a depositor, keeping largest_deposits in sync
+    fn set_deposit(&mut self, account_id: &AccountId, balance: Balance) {
+        let current_balance = self.deposits_of(account_id);
+
+        // A zero balance key is only held by the entries reserved by reserve_storage
+        self.largest_deposits
+            .remove(&(current_balance, account_id.clone()));
+
+        if balance > 0 {
+            self.largest_deposits
+                .insert(&(balance, account_id.clone()), &());
+        }
+
+        self.deposits.insert(account_id, &balance);
+    }
+
+    /// Drops the entry of a depositor, see set_deposit
+    fn remove_deposit(&mut self, account_id: &AccountId) {
+        self.set_deposit(account_id, 0);
+        self.deposits.remove(account_id);
+    }
+
+    /// Deposits of payee whose cooling-off period has not ended, without the entry reserved by
+    /// reserve_storage
+    fn get_cooling_off_deposits_of(&self, payee: &AccountId) -> Vec<(u64, Balance)> {
+        let mut cooling_off_deposits = self.cooling_off_deposits.get(payee).unwrap_or_default();
+        cooling_off_deposits
+            .retain(|(ends_at, amount)| *amount > 0 && env::block_timestamp() <= *ends_at);
+        cooling_off_deposits
+    }
+
+    /// Inserts zeroed entries for everything the first deposit of payee, or the first position it
+    /// receives, may write, and returns the cost of the storage they use. The values are fixed
+    /// size, so writing the actual ones uses no more storage
+    fn reserve_storage(&mut self, payee: &AccountId, is_deposit: bool) -> Balance {
+        let initial_storage_usage = env::storage_usage();
+
+        self.deposits.insert(payee, &0);
+        self.storage_fees.insert(payee, &0);
+        self.largest_deposits.insert(&(0, payee.clone()), &());
+
+        let has_bonus = match is_deposit {
+            true => self.get_current_bonus_basis_points() > 0,
+            false => self.total_bonus_weight > 0,
+        };
+
+        if has_bonus && self.bonus_weights.get(payee).is_none() {
+            self.bonus_weights.insert(payee, &0);
+        }
+
+        // Extension ids start at 1, the reserved vote is never a current one
+        if self.extension_quorum_basis_points.is_some() && self.extension_votes.get(payee).is_none()
+        {
+            self.extension_votes.insert(payee, &(0, false));
+        }
+
+        if is_deposit {
+            if self.cooling_off_period > 0 {
+                let mut cooling_off_deposits = self.get_cooling_off_deposits_of(payee);
+                cooling_off_deposits.push((0, 0));
+                self.cooling_off_deposits
+                    .insert(payee, &cooling_off_deposits);
+            }
+
+            let is_matched = matches!(&self.sponsor_account_id, Some(account_id) if account_id != payee)
+                && self.match_pool > 0;
+
+            if is_matched && self.matches.get(payee).is_none() {
+                self.matches.insert(payee, &0);
+            }
+        }
+
+        let storage_fee =
+            Balance::from(env::storage_usage() - initial_storage_usage) * env::storage_byte_cost();
+
+        self.storage_fees.insert(payee, &storage_fee);
+        self.total_storage_fees += storage_fee;
+
+        storage_fee
+    }
+
+    /// Undoes reserve_storage for a depositor whose first deposit was not recorded
+    fn release_storage(&mut self, payee: &AccountId) {
+        if let Some(storage_fee) = self.storage_fees.remove(payee) {
+            self.total_storage_fees -= storage_fee;
+            self.remove_deposit(payee);
+
+            if self.bonus_weights.get(payee) == Some(0) {
+                self.bonus_weights.remove(payee);
+            }
+
+            if matches!(self.extension_votes.get(payee), Some((0, _))) {
+                self.extension_votes.remove(payee);
+            }
+
+            match self.get_cooling_off_deposits_of(payee) {
+                cooling_off_deposits if cooling_off_deposits.is_empty() => {
+                    self.cooling_off_deposits.remove(payee)
+                }
+                cooling_off_deposits => self
+                    .cooling_off_deposits
+                    .insert(payee, &cooling_off_deposits),
+            };
+
+            if self.matches.get(payee) == Some(0) {
+                self.matches.remove(payee);
+            }
+        }
+    }
+
+    /// Drops the storage fee of payee once its entries were released since
+    /// initial_storage_usage. Only the cost of the released storage is refunded, the rest of the
+    /// fee covers the entries that are kept
+    fn take_storage_fee(
+        &mut self,
+        payee: &AccountId,
+        initial_storage_usage: StorageUsage,
+    ) -> Option<Balance> {
+        let storage_fee = self.storage_fees.remove(payee)?;
+        self.total_storage_fees -= storage_fee;
+
+        let released_storage =
+            Balance::from(initial_storage_usage.saturating_sub(env::storage_usage()))
+                * env::storage_byte_cost();
+
+        Some(storage_fee.min(released_storage))
+    }
+
+    /// Charges the storage fee of a new depositor and checks the rest of amount. Returns the
+    /// amount to record and the surplus to refund, the storage is released if the check fails
+    fn prepare_deposit(
+        &mut self,
+        payee: &AccountId,
+        amount: Balance,
+    ) -> Result<(Balance, Balance), &'static str> {
+        let storage_fee = match self.is_storage_fee_charged && self.deposits.get(payee).is_none() {
+            true => self.reserve_storage(payee, true),
+            false => 0,
+        };
+
+        let result = match amount.checked_sub(storage_fee) {
+            Some(amount) if storage_fee == 0 || amount > 0 => self
+                .check_deposit(payee, amount)
+                .map(|surplus| (amount - surplus, surplus)),
+            _ => Err("ERR_INSUFFICIENT_STORAGE_FEE"),
+        };
+
+        if result.is_err() && storage_fee > 0 {
+            self.release_storage(payee);
+        }
+
+        result
+    }
+
+    /// Records the deposit up to the unpaid funding amount, returns the surplus to refund
+    fn internal_deposit(&mut self, payee: AccountId, amount: Balance) -> Balance {
+        self.update_status();
+
+        match self.try_internal_deposit(payee, amount) {
+            Ok(surplus) => surplus,
+            Err(err) => env::panic_str(err),
+        }
+    }
+
+    /// Records the deposit like internal_deposit, without any change if it is not allowed
+    fn try_internal_deposit(
+        &mut self,
+        payee: AccountId,
+        amount: Balance,
+    ) -> Result<Balance, &'static str> {
+        let (amount, surplus) = self.prepare_deposit(&payee, amount)?;
+
+        let current_balance = self.deposits_of(&payee);
+        let new_balance = &(current_balance.wrapping_add(amount));
+
+        self.set_deposit(&payee, *new_balance);
+        self.update_extension_vote(&payee, current_balance, *new_balance);
+        self.add_bonus_weight(&payee, amount);
+
+        // Each deposit has its own cooling-off period, the ended ones are dropped
+        if self.cooling_off_period > 0 {
+            let mut cooling_off_deposits = self.get_cooling_off_deposits_of(&payee);
+            cooling_off_deposits.push((env::block_timestamp() + self.cooling_off_period, amount));
+            self.cooling_off_deposits
+                .insert(&payee, &cooling_off_deposits);
+        }
+        self.total_funds = self.total_funds.wrapping_add(amount);
+        self.raised_funds = self.raised_funds.wrapping_add(amount);
+        self.unpaid_funding_amount = self.funding_amount_limit.saturating_sub(self.raised_funds);
+        self.match_deposit(&payee, amount);
+
+        if !self.is_oversubscription_allowed && self.is_funding_reached() {
+            self.set_status(CampaignStatus::Funded);
+        }
+
+        log!(
+            "{} deposited {} NEAR tokens. New balance {} — Total funds: {} — Unpaid funds: {}",
+            &payee,
+            amount,
+            new_balance,
+            self.total_funds,
+            self.unpaid_funding_amount
+        );
+
+        if surplus > 0 {
+            log!(
+                "{} refunded {} NEAR tokens over the funding amount limit",
+                &payee,
+                surplus
+            );
+        }
+
+        EscrowEvent::Deposit(DepositEvent {
+            account_id: payee,
+            amount: U128(amount),
+            balance: U128(*new_balance),
+            total_funds: U128(self.total_funds),
+            unpaid_funding_amount: U128(self.unpaid_funding_amount),
+        })
+        .emit();
+
+        Ok(surplus)
+    }
+
+    /// The callback pays the fee and the keeper reward, with a token they are ft_transfer calls
+    /// and the DAO funds are forwarded too
+    fn get_delegate_callback_gas(&self) -> Gas {
+        let mut gas = GAS_FOR_DELEGATE_CALLBACK;
+        let payouts_count =
+            (self.fee_basis_points > 0) as u64 + (self.keeper_reward_basis_points > 0) as u64;
+
+        gas += GAS_FOR_PAYOUT_CALLBACK * payouts_count;
+
+        if self.funding_token_id.is_some() {
+            gas += GAS_FOR_FORWARD_FUNDS + GAS_FOR_FT_TRANSFER * payouts_count;
+        }
+
+        gas
+    }
+
+    /// Transfers NEAR or, when set, the funding token
+    fn transfer_funds(&self, receiver_id: &AccountId, amount: Balance) -> Promise {
+        match &self.funding_token_id {
+            Some(funding_token_id) => Promise::new(funding_token_id.clone()).function_call(
+                "ft_transfer".to_string(),
+                json!({"receiver_id": receiver_id, "amount": U128(amount)})
+                    .to_string()
+                    .into_bytes(),
+                ONE_YOCTO,
+                GAS_FOR_FT_TRANSFER,
+            ),
+            None => Promise::new(receiver_id.clone()).transfer(amount),
+        }
+    }
+
+    /// Pays an account other than a depositor, on_payout_callback keeps the payout claimable if
+    /// the transfer fails
+    fn transfer_payout(&self, receiver_id: &AccountId, amount: Balance) -> Promise {
+        self.transfer_funds(receiver_id, amount).then(
+            Promise::new(env::current_account_id()).function_call(
+                "on_payout_callback".to_string(),
+                json!({"account_id": receiver_id, "amount": U128(amount)})
+                    .to_string()
+                    .into_bytes(),
+                0,
+                GAS_FOR_PAYOUT_CALLBACK,
+            ),
+        )
+    }
+
+    fn set_status(&mut self, status: CampaignStatus) {
+        if !self.status.can_transition_to(status) {
+            env::panic_str("ERR_INVALID_STATUS_TRANSITION");
+        }
+
+        EscrowEvent::StatusChanged(StatusChangedEvent {
+            previous_status: self.status,
+            status,
+        })
+        .emit();
+
+        self.status = status;
+    }
+
+    /// Persists the time based transitions
+    fn update_status(&mut self) {
+        let status = self.get_status();
+
+        if status == self.status {
+            return;
+        }
+
+        // on_delegate_callback never completed, eg. it ran out of gas. The outcome of the creations
+        // is unknown, so the keeper deposit is not refunded
+        if self.status == CampaignStatus::Delegating {
+            self.delegation_failed_at = self.get_delegation_failed_at();
+
+            if !self.is_dao_created {
+                self.fee_amount = 0;
+                self.keeper_reward = 0;
+                self.excess_dust = 0;
+                self.keeper_deposit = 0;
+            }
+
+            log!("Delegation timed out without a callback");
+        }
+
+        self.set_status(status);
+    }
+
+    /// Deposit minus its pro-rata share of the delegation costs
+    fn get_refund_amount(&self, deposit: Balance) -> Balance {
+        if self.delegation_costs == 0 {
+            return deposit;
+        }
+
+        // The early exit penalties and the matched funds kept in the pot are not refunded
+        let funds = self.total_funds - self.get_pot_funds();
+
+        (U256::from(deposit) * U256::from(funds - self.delegation_costs) / U256::from(funds))
+            .as_u128()
+    }
+
+    fn has_delegation_retry_period_ended(&self) -> bool {
+        self.get_delegation_failed_at() + DELEGATION_RETRY_PERIOD < env::block_timestamp()
+    }
+
+    fn has_delegation_timed_out(&self) -> bool {
+        self.delegation_started_at + DELEGATION_CALLBACK_TIMEOUT < env::block_timestamp()
+    }
+
+    fn set_release_condition_met(&mut self, index: u32) {
+        if self.met_release_conditions[index as usize] {
+            return;
+        }
+
+        self.met_release_conditions[index as usize] = true;
+
+        log!("Release condition {} met", index);
+
+        EscrowEvent::ReleaseConditionMet(ReleaseConditionMetEvent {
+            index,
+            condition: self.release_conditions[index as usize].clone(),
+        })
+        .emit();
+    }
+
+    fn has_release_conditions_deadline_passed(&self) -> bool {
+        !self.release_conditions.is_empty()
+            && self.release_conditions_deadline < env::block_timestamp()
+    }
+
+    fn has_contract_expired(&self) -> bool {
+        self.expires_at < env::block_timestamp().try_into().unwrap()
+    }
+
+    fn is_funding_reached(&self) -> bool {
+        self.get_raised_funds() >= self.get_funding_amount_limit()
+    }
+
+    fn is_soft_cap_reached(&self) -> bool {
+        self.get_raised_funds() >= self.get_soft_cap()
+    }
+
+    /// Pro-rata share of the funds raised over the funding_amount_limit, rounded down
+    fn get_oversubscribed_amount(&self, deposit: Balance) -> Balance {
+        if self.raised_funds <= self.funding_amount_limit {
+            return 0;
+        }
+
+        (U256::from(deposit) * U256::from(self.raised_funds - self.funding_amount_limit)
+            / U256::from(self.raised_funds))
+        .as_u128()
+    }
+
+    /// Funds raised over the funding_amount_limit that are not part of the excess of any depositor,
+    /// since each excess is rounded down
+    fn compute_excess_dust(&self) -> Balance {
+        if self.raised_funds <= self.funding_amount_limit {
+            return 0;
+        }
+
+        let excess: Balance = self
+            .deposits
+            .values()
+            .map(|deposit| self.get_oversubscribed_amount(deposit))
+            .sum();
+
+        self.raised_funds - self.funding_amount_limit - excess
+    }
+
+    fn is_promise_result_true(result: &PromiseResult) -> bool {
+        match result {
+            PromiseResult::Successful(result) => {
+                near_sdk::serde_json::from_slice::<bool>(result).unwrap_or(false)
+            }
+            _ => false,
+        }
+    }
+
+    /// Checks the min_deposit and max_deposit_per_account bounds of a balance
+    fn assert_deposit_limits(&self, balance: Balance) {
+        if balance > 0 && balance < self.min_deposit {
+            env::panic_str("ERR_MIN_DEPOSIT_NOT_REACHED");
+        }
+
+        if let Some(max_deposit) = self.max_deposit_per_account {
+            if balance > max_deposit {
+                env::panic_str("ERR_MAX_DEPOSIT_PER_ACCOUNT_EXCEEDED");
+            }
+        }
+    }
+
+    fn internal_transfer_position(
+        &mut self,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        amount: Balance,
+    ) {
+        if !self.is_position_transfer_allowed() {
+            env::panic_str("ERR_POSITION_TRANSFER_NOT_ALLOWED");
+        }
+
+        if amount == 0 || amount > self.deposits_of(&sender_id) {
+            env::panic_str("ERR_INVALID_POSITION_AMOUNT");
+        }
+
+        if self.is_sponsor_position(&sender_id) {
+            env::panic_str("ERR_POSITION_TRANSFER_NOT_ALLOWED");
+        }
+
+        // A new receiver is charged the storage fee out of the position, like a first deposit
+        let storage_fee =
+            match self.is_storage_fee_charged && self.deposits.get(&receiver_id).is_none() {
+                true => self.reserve_storage(&receiver_id, false),
+                false => 0,
+            };
+
+        let received_amount = match amount.checked_sub(storage_fee) {
+            Some(received_amount) if storage_fee == 0 || received_amount > 0 => received_amount,
+            _ => env::panic_str("ERR_INSUFFICIENT_STORAGE_FEE"),
+        };
+
+        let sender_balance = self.deposits_of(&sender_id) - amount;
+        let receiver_balance = self.deposits_of(&receiver_id) + received_amount;
+
+        self.assert_deposit_limits(sender_balance);
+        self.assert_deposit_limits(receiver_balance);
+
+        if self.is_allowlist_enabled && !self.allowlist.contains(&receiver_id) {
+            env::panic_str("ERR_NOT_ALLOWLISTED");
+        }
+
+        // Storage released by the sender from here on, refunded if it transfers its whole position
+        let initial_storage_usage = env::storage_usage();
+
+        // The early-backer bonus follows the position pro-rata
+        let sender_bonus = self.get_bonus_weight_of(&sender_id);
+        let bonus = (U256::from(sender_bonus) * U256::from(amount)
+            / U256::from(sender_balance + amount))
+        .as_u128();
+
+        if bonus > 0 {
+            match sender_bonus - bonus {
+                0 => self.bonus_weights.remove(&sender_id),
+                sender_bonus => self.bonus_weights.insert(&sender_id, &sender_bonus),
+            };
+            self.bonus_weights.insert(
+                &receiver_id,
+                &(self.get_bonus_weight_of(&receiver_id) + bonus),
+            );
+        }
+
+        // The cooling-off period only covers funds the sender still holds, transferred funds never
+        // join the cooling-off period of the receiver. The earliest deposits are transferred first
+        let mut cooling_off_deposits = self.get_cooling_off_deposits_of(&sender_id);
+        let mut excess = self
+            .get_cooling_off_amount_of(&sender_id)
+            .saturating_sub(sender_balance);
+
+        if excess > 0 {
+            for (_, amount) in cooling_off_deposits.iter_mut() {
+                let transferred = excess.min(*amount);
+                *amount -= transferred;
+                excess -= transferred;
+            }
+
+            cooling_off_deposits.retain(|(_, amount)| *amount > 0);
+
+            match cooling_off_deposits.is_empty() {
+                true => self.cooling_off_deposits.remove(&sender_id),
+                false => self
+                    .cooling_off_deposits
+                    .insert(&sender_id, &cooling_off_deposits),
+            };
+        }
+
+        // total_funds and raised_funds are unchanged, but for the storage fee of a new receiver
+        self.set_deposit(&sender_id, sender_balance);
+        self.set_deposit(&receiver_id, receiver_balance);
+        self.update_extension_vote(&sender_id, sender_balance + amount, sender_balance);
+        self.update_extension_vote(
+            &receiver_id,
+            receiver_balance - received_amount,
+            receiver_balance,
+        );
+
+        if storage_fee > 0 {
+            self.total_funds -= storage_fee;
+            self.raised_funds -= storage_fee;
+            self.unpaid_funding_amount =
+                self.funding_amount_limit.saturating_sub(self.raised_funds);
+        }
+
+        // The entry of a sender that transferred its whole position is dropped with its storage fee
+        if sender_balance == 0 && self.storage_fees.contains_key(&sender_id) {
+            self.remove_deposit(&sender_id);
+
+            match self.take_storage_fee(&sender_id, initial_storage_usage) {
+                Some(storage_fee) if storage_fee > 0 => {
+                    self.transfer_payout(&sender_id, storage_fee);
+                }
+                _ => {}
+            }
+        }
+
+        log!(
+            "{} transferred a position of {} NEAR tokens to {}",
+            &sender_id,
+            amount,
+            &receiver_id
+        );
+
+        EscrowEvent::PositionTransferred(PositionTransferredEvent {
+            sender_id,
+            receiver_id,
+            amount: U128(amount),
+            sender_balance: U128(sender_balance),
+            receiver_balance: U128(receiver_balance),
+        })
+        .emit();
+    }
+
+    fn get_current_extension_vote(&self, account_id: &AccountId) -> Option<bool> {
+        match self.extension_votes.get(account_id) {
+            Some((extension_id, approve))
+                if extension_id > 0 && extension_id == self.deadline_extensions_count =>
+            {
+                Some(approve)
+            }
+            _ => None,
+        }
+    }
+
+    /// Moves the weight of a vote already cast when the balance of the voter changes
+    fn update_extension_vote(
+        &mut self,
+        account_id: &AccountId,
+        previous: Balance,
+        balance: Balance,
+    ) {
+        let approve = match self.get_current_extension_vote(account_id) {
+            Some(approve) => approve,
+            None => return,
+        };
+
+        if let Some(extension) = self.deadline_extension.as_mut() {
+            let votes = match approve {
+                true => &mut extension.votes_for,
+                false => &mut extension.votes_against,
+            };
+
+            votes.0 = votes.0 - previous + balance;
+        }
+    }
+
+    /// Votes cast reach the quorum of the raised funds and the votes for exceed the threshold
+    fn is_deadline_extension_approved(&self, extension: &DeadlineExtension) -> bool {
+        let quorum = U256::from(self.extension_quorum_basis_points.unwrap_or(0));
+        let threshold = U256::from(self.extension_threshold_basis_points);
+        let max = U256::from(MAX_EXTENSION_BASIS_POINTS);
+
+        let votes_for = U256::from(extension.votes_for.0);
+        let votes = votes_for + U256::from(extension.votes_against.0);
+
+        votes * max >= quorum * U256::from(self.raised_funds) && votes_for * max > threshold * votes
+    }
+
+    fn get_deposit_accounts(&self) -> Vec<String> {
+        let mut accounts = vec![];
+
+        for account_id in self.deposits.keys() {
+            if self.get_allocation_of(&account_id) > 0 {
+                accounts.push(account_id.to_string());
+            }
+        }
+
+        accounts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use near_sdk::test_utils::test_env::{alice, bob, carol};
+    use near_sdk::test_utils::{accounts, get_logs, VMContextBuilder};
+    use near_sdk::{testing_env, PromiseResult};
+
+    const ATTACHED_DEPOSIT: Balance = 1_000_000_000_000_000_000_000_000; // 1 Near
+    const MIN_FUNDING_AMOUNT: Balance = 15_000_000_000_000_000_000_000_000; // 15 Near
+    const FEE_BASIS_POINTS: u16 = 150; // 1.5%
+
+    fn setup_context() -> VMContextBuilder {
+        let mut context = VMContextBuilder::new();
+        let now = Utc::now().timestamp_subsec_nanos();
+        testing_env!(context
+            .predecessor_account_id(alice())
+            .block_timestamp(now.try_into().unwrap())
+            .build());
+
+        context
+    }
+
+    fn setup_contract_with(
+        expires_at: u64,
+        funding_amount_limit: u128,
+        config: CampaignConfig,
+    ) -> ConditionalEscrow {
+        ConditionalEscrow::new(
+            expires_at,
+            U128(funding_amount_limit),
+            accounts(3),
+            accounts(4),
+            "metadata_url.json".to_string(),
+            Some(config),
+        )
+    }
+
+    fn setup_contract(expires_at: u64, funding_amount_limit: u128) -> ConditionalEscrow {
+        setup_contract_with(expires_at, funding_amount_limit, CampaignConfig::default())
+    }
+
+    fn add_expires_at_nanos(offset: u32) -> u64 {
+        let now = Utc::now().timestamp_subsec_nanos();
+        (now + offset).into()
+    }
+
+    fn substract_expires_at_nanos(offset: u32) -> u64 {
+        let now = Utc::now().timestamp_subsec_nanos();
+        (now - offset).into()
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INSUFFICIENT_FUNDS_LIMIT")]
+    fn test_new_fail() {
+        let mut context = setup_context();
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(0)
+            .build());
+
+        let expires_at = add_expires_at_nanos(100);
+
+        // Should fail because insufficient funds limit
+        setup_contract(expires_at, ATTACHED_DEPOSIT);
+    }
+
+    #[test]
+    fn test_get_deposits_of() {
+        let expires_at = add_expires_at_nanos(100);
+
+        let contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        assert_eq!(
+            0,
+            contract.deposits_of(&alice()),
+            "Account deposits should be 0"
+        );
+    }
+
+    #[test]
+    fn test_get_shares_of() {
+        let mut context = setup_context();
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        contract.deposit();
+
+        assert_eq!(
+            0,
+            contract.get_shares_of(&alice()),
+            "Account deposits should be 0"
+        );
+
+        assert_eq!(
+            1000,
+            contract.get_shares_of(&bob()),
+            "Bob deposited all the raised funds"
+        );
+    }
+
+    #[test]
+    fn test_get_deposits() {
+        let mut context = setup_context();
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        contract.deposit();
+
+        assert_eq!(
+            contract.get_deposits(),
+            vec![(bob(), ATTACHED_DEPOSIT)],
+            "Gets all deposits as vec"
+        );
+    }
+
+    #[test]
+    fn test_get_deposit_accounts() {
+        let mut context = setup_context();
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+
+        assert_eq!(
+            vec!["bob.near", "carol.near"],
+            contract.get_deposit_accounts(),
+        );
+    }
+
+    #[test]
+    fn test_get_dao_factory_account_id() {
+        let expires_at = add_expires_at_nanos(100);
+
+        let contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        assert_eq!(
+            accounts(3),
+            contract.get_dao_factory_account_id(),
+            "Recipient account id should be 'danny.near'"
+        );
+    }
+
+    #[test]
+    fn test_get_ft_factory_account_id() {
+        let expires_at = add_expires_at_nanos(100);
+
+        let contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        assert_eq!(
+            accounts(4),
+            contract.get_ft_factory_account_id(),
+            "Recipient account id should be 'eugene.near'"
+        );
+    }
+
+    #[test]
+    fn test_get_dao_name() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT / 2)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(MIN_FUNDING_AMOUNT / 2)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .block_timestamp((expires_at + 200).try_into().unwrap())
+            .build());
+
+        contract.delegate_funds("dao1".to_string());
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful("true".to_string().into_bytes()),
+                PromiseResult::Successful("true".to_string().into_bytes())
+            ],
+        );
+
+        assert_eq!(
+            contract.on_delegate_callback("dao1".to_string()),
+            true,
+            "delegate_funds should run successfully"
+        );
+
+        assert_eq!("dao1", contract.get_dao_name(), "Should equal DAO Name");
+    }
+
+    #[test]
+    fn test_get_metadata_url() {
+        let expires_at = add_expires_at_nanos(100);
+
+        let contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        assert_eq!(
+            "metadata_url.json",
+            contract.get_metadata_url(),
+            "Contract was not initilialized with metadata_url param"
+        );
+    }
+
+    #[test]
+    fn test_get_0_total_funds() {
+        let expires_at = add_expires_at_nanos(100);
+
+        let contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        assert_eq!(0, contract.get_total_funds(), "Total funds should be 0");
+    }
+
+    #[test]
+    fn test_get_correct_unpaid_funding_amount() {
+        let mut context = setup_context();
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        contract.deposit();
+
+        assert_eq!(
+            MIN_FUNDING_AMOUNT - ATTACHED_DEPOSIT,
+            contract.get_unpaid_funding_amount(),
+            "Unpaid funding amount is wrong"
+        );
+    }
+
+    #[test]
+    fn test_get_total_funds_after_deposits() {
+        let mut context = setup_context();
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+
+        assert_eq!(
+            ATTACHED_DEPOSIT * 2,
+            contract.get_total_funds(),
+            "Total funds should be ATTACHED_DEPOSITx2"
+        );
+    }
+
+    #[test]
+    fn test_is_withdrawal_allowed() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT * 2);
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT - 1_000)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(MIN_FUNDING_AMOUNT - 1_000)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .block_timestamp((expires_at + 100).try_into().unwrap())
+            .build());
+
+        contract.withdraw();
+
+        testing_env!(context.predecessor_account_id(carol()).build());
+
+        contract.withdraw();
+
+        assert_eq!(
+            true,
+            contract.is_withdrawal_allowed(),
+            "Withdrawal should be allowed"
+        );
+
+        assert_eq!(0, contract.get_total_funds(), "Total funds should be 0");
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DEPOSIT_SHOULD_NOT_BE_0")]
+    fn test_deposits() {
+        let mut context = setup_context();
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(0)
+            .build());
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        contract.deposit();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_WITHDRAWAL_NOT_ALLOWED")]
+    fn test_is_withdrawal_not_allowed() {
+        setup_context();
+        let expires_at = add_expires_at_nanos(1_000_000);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        contract.withdraw();
+
+        assert_eq!(
+            false,
+            contract.is_withdrawal_allowed(),
+            "Withdrawal should not be allowed"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DEPOSIT_NOT_ALLOWED")]
+    fn test_is_deposit_not_allowed_by_expiration_date() {
+        let mut context = setup_context();
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        let expires_at = substract_expires_at_nanos(5_000_000);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        contract.deposit();
+
+        assert_eq!(
+            false,
+            contract.is_deposit_allowed(),
+            "Deposit should not be allowed"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DEPOSIT_NOT_ALLOWED")]
+    fn test_is_deposit_not_allowed_by_total_funds_reached() {
+        let mut context = setup_context();
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT)
+            .build());
+
+        let expires_at = add_expires_at_nanos(1_000_000);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+
+        assert_eq!(
+            false,
+            contract.is_deposit_allowed(),
+            "Deposit should not be allowed"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_OWNER_SHOULD_NOT_DEPOSIT")]
+    fn test_owner_deposit() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        testing_env!(context
+            .predecessor_account_id(alice())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DELEGATE_NOT_ALLOWED")]
+    fn test_should_not_delegate_funds_if_active() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+
+        assert_eq!(
+            true,
+            contract.is_deposit_allowed(),
+            "Deposit should be allowed"
+        );
+
+        assert_eq!(
+            false,
+            contract.is_withdrawal_allowed(),
+            "Withdrawal should not be allowed"
+        );
+
+        contract.delegate_funds("dao1".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DELEGATE_NOT_ALLOWED")]
+    fn test_should_not_delegate_funds_if_expired() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT - 1_000)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .block_timestamp((expires_at + 200).try_into().unwrap())
+            .build());
+
+        assert_eq!(
+            false,
+            contract.is_deposit_allowed(),
+            "Deposit should not be allowed"
+        );
+
+        assert_eq!(
+            true,
+            contract.is_withdrawal_allowed(),
+            "Withdrawal should be allowed"
+        );
+
+        contract.delegate_funds("dao1".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DELEGATE_NOT_ALLOWED")]
+    fn test_should_not_delegate_funds_if_already_delegated() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT / 2)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(MIN_FUNDING_AMOUNT / 2)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .block_timestamp((expires_at + 200).try_into().unwrap())
+            .build());
+
+        assert_eq!(
+            false,
+            contract.is_deposit_allowed(),
+            "Deposit should not be allowed"
+        );
+
+        assert_eq!(
+            false,
+            contract.is_withdrawal_allowed(),
+            "Withdrawal should not be allowed"
+        );
+
+        contract.delegate_funds("dao1".to_string());
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful("true".to_string().into_bytes()),
+                PromiseResult::Successful("true".to_string().into_bytes())
+            ],
+        );
+
+        assert_eq!(
+            contract.on_delegate_callback("dao1".to_string()),
+            true,
+            "delegate_funds should run successfully"
+        );
+
+        assert_eq!(0, contract.get_total_funds(), "Total funds should be 0");
+
+        contract.delegate_funds("dao1".to_string());
+    }
+
+    #[test]
+    fn test_should_not_delegate_funds_if_create_dao_fails() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT / 2)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(MIN_FUNDING_AMOUNT / 2)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .block_timestamp((expires_at + 200).try_into().unwrap())
+            .build());
+
+        assert_eq!(
+            false,
+            contract.is_deposit_allowed(),
+            "Deposit should not be allowed"
+        );
+
+        assert_eq!(
+            false,
+            contract.is_withdrawal_allowed(),
+            "Withdrawal should not be allowed"
+        );
+
+        contract.delegate_funds("dao1".to_string());
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed, PromiseResult::Failed],
+        );
+
+        assert_eq!(
+            contract.on_delegate_callback("dao1".to_string()),
+            false,
+            "delegate_funds should fail"
+        );
+
+        assert_eq!(
+            MIN_FUNDING_AMOUNT,
+            contract.get_total_funds(),
+            "Total funds should be MIN_FUNDING_AMOUNT"
+        );
+
+        assert_eq!(CampaignStatus::DelegationFailed, contract.get_status());
+    }
+
+    #[test]
+    fn test_delegate_funds() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT / 2)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(MIN_FUNDING_AMOUNT / 2)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .block_timestamp((expires_at + 200).try_into().unwrap())
+            .build());
+
+        assert_eq!(
+            false,
+            contract.is_deposit_allowed(),
+            "Deposit should not be allowed"
+        );
+
+        assert_eq!(
+            false,
+            contract.is_withdrawal_allowed(),
+            "Withdrawal should not be allowed"
+        );
+
+        contract.delegate_funds("dao1".to_string());
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful("true".to_string().into_bytes()),
+                PromiseResult::Successful("true".to_string().into_bytes())
+            ],
+        );
+
+        assert_eq!(
+            contract.on_delegate_callback("dao1".to_string()),
+            true,
+            "delegate_funds should run successfully"
+        );
+
+        assert_eq!(0, contract.get_total_funds(), "Total funds should be 0");
+
+        assert_eq!(
+            MIN_FUNDING_AMOUNT / 2,
+            contract.deposits_of(&bob()),
+            "Account deposits should be MIN_FUNDING_AMOUNT"
+        );
+
+        assert_eq!(
+            MIN_FUNDING_AMOUNT / 2,
+            contract.deposits_of(&carol()),
+            "Account deposits should be MIN_FUNDING_AMOUNT"
+        );
+    }
+
+    #[test]
+    fn test_delegate_funds_ft_treasury_is_the_creator() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .attached_deposit(0)
+            .block_timestamp(expires_at + 200)
+            .build());
+
+        contract.delegate_funds("dao1".to_string());
+
+        let create_ft_args: Vec<near_sdk::serde_json::Value> =
+            near_sdk::test_utils::get_created_receipts()
+                .iter()
+                .flat_map(|receipt| receipt.actions.iter())
+                .filter_map(|action| match action {
+                    near_sdk::mock::VmAction::FunctionCall {
+                        function_name,
+                        args,
+                        ..
+                    } if function_name == "create_ft" => {
+                        near_sdk::serde_json::from_slice(args).ok()
+                    }
+                    _ => None,
+                })
+                .collect();
+
+        // The rounding dust of the token claims goes to the creator, not to the DAO account
+        assert_eq!(
+            vec![json!({"name": "dao1", "treasury_account_id": accounts(5)})],
+            create_ft_args
+        );
+    }
+
+    #[test]
+    fn test_deposit_event() {
+        let mut context = setup_context();
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        contract.deposit();
+
+        let event = EscrowEvent::Deposit(DepositEvent {
+            account_id: bob(),
+            amount: U128(ATTACHED_DEPOSIT),
+            balance: U128(ATTACHED_DEPOSIT),
+            total_funds: U128(ATTACHED_DEPOSIT),
+            unpaid_funding_amount: U128(MIN_FUNDING_AMOUNT - ATTACHED_DEPOSIT),
+        });
+
+        assert_eq!(
+            get_logs().last().unwrap(),
+            &format!("EVENT_JSON:{}", event.to_json_string()),
+            "Should emit a deposit event"
+        );
+
+        assert_eq!(
+            event.to_json_string(),
+            format!(
+                r#"{{"standard":"nearholdings","version":"1.0.0","event":"deposit","data":{{"account_id":"bob.near","amount":"{}","balance":"{}","total_funds":"{}","unpaid_funding_amount":"{}"}}}}"#,
+                ATTACHED_DEPOSIT,
+                ATTACHED_DEPOSIT,
+                ATTACHED_DEPOSIT,
+                MIN_FUNDING_AMOUNT - ATTACHED_DEPOSIT
+            ),
+            "Event should follow NEP-297"
+        );
+    }
+
+    #[test]
+    fn test_withdraw_event() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.block_timestamp(expires_at + 100).build());
+
+        contract.withdraw();
+
+        let event = EscrowEvent::Withdraw(WithdrawEvent {
+            account_id: bob(),
+            amount: U128(ATTACHED_DEPOSIT),
+            balance: U128(0),
+            total_funds: U128(0),
+            unpaid_funding_amount: U128(MIN_FUNDING_AMOUNT),
+        });
+
+        assert_eq!(
+            get_logs().last().unwrap(),
+            &format!("EVENT_JSON:{}", event.to_json_string()),
+            "Should emit a withdraw event"
+        );
+    }
+
+    #[test]
+    fn test_delegate_events() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT / 2)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(MIN_FUNDING_AMOUNT / 2)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.block_timestamp(expires_at + 200).build());
+
+        contract.delegate_funds("dao1".to_string());
+
+        let event = EscrowEvent::DelegateStarted(DelegateStartedEvent {
+            dao_name: "dao1".to_string(),
+            dao_amount: U128(MIN_FUNDING_AMOUNT - FT_ATTACHED_DEPOSIT),
+            ft_amount: U128(FT_ATTACHED_DEPOSIT),
+            fee_amount: U128(0),
+            depositors_count: 2,
+        });
+
+        assert!(
+            get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())),
+            "Should emit a delegate_started event"
+        );
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful("true".to_string().into_bytes()),
+                PromiseResult::Successful("true".to_string().into_bytes())
+            ],
+        );
+
+        contract.on_delegate_callback("dao1".to_string());
+
+        let event = EscrowEvent::DelegateSucceeded(DelegateSucceededEvent {
+            dao_name: "dao1".to_string(),
+        });
+
+        assert!(
+            get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())),
+            "Should emit a delegate_succeeded event"
+        );
+    }
+
+    #[test]
+    fn test_delegate_failed_event() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.block_timestamp(expires_at + 200).build());
+
+        contract.delegate_funds("dao1".to_string());
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful("true".to_string().into_bytes()),
+                PromiseResult::Successful("false".to_string().into_bytes())
+            ],
+        );
+
+        assert!(
+            !contract.on_delegate_callback("dao1".to_string()),
+            "delegate_funds should fail"
+        );
+
+        let event = EscrowEvent::DelegateFailed(DelegateFailedEvent {
+            dao_name: "dao1".to_string(),
+            is_dao_created: true,
+            is_ft_created: false,
+        });
+
+        assert!(
+            get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())),
+            "Should emit a delegate_failed event"
+        );
+    }
+
+    #[test]
+    fn test_ft_on_transfer() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                funding_token_id: Some(accounts(5)),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+
+        match contract.ft_on_transfer(bob(), U128(ATTACHED_DEPOSIT), "".to_string()) {
+            PromiseOrValue::Value(unused_amount) => {
+                assert_eq!(0, unused_amount.0, "All tokens should be used")
+            }
+            _ => panic!("ft_on_transfer should return a value"),
+        }
+
+        assert_eq!(Some(accounts(5)), contract.get_funding_token_id());
+
+        assert_eq!(
+            ATTACHED_DEPOSIT,
+            contract.deposits_of(&bob()),
+            "Account deposits should equal ATTACHED_DEPOSIT"
+        );
+
+        assert_eq!(
+            ATTACHED_DEPOSIT,
+            contract.get_total_funds(),
+            "Total funds should equal ATTACHED_DEPOSIT"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_FUNDING_TOKEN")]
+    fn test_ft_on_transfer_invalid_token() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                funding_token_id: Some(accounts(5)),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context.predecessor_account_id(bob()).build());
+
+        contract.ft_on_transfer(bob(), U128(ATTACHED_DEPOSIT), "".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_FUNDING_TOKEN_REQUIRED")]
+    fn test_near_deposit_with_funding_token() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                funding_token_id: Some(accounts(5)),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+    }
+
+    #[test]
+    fn test_ft_withdraw_restores_balance_on_failure() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                funding_token_id: Some(accounts(5)),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+
+        contract.ft_on_transfer(bob(), U128(ATTACHED_DEPOSIT), "".to_string());
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .block_timestamp(expires_at + 100)
+            .build());
+
+        contract.withdraw();
+
+        assert_eq!(0, contract.deposits_of(&bob()), "Balance should be 0");
+
+        testing_env!(
+            context.predecessor_account_id(alice()).build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+
+        assert!(
+            !contract.on_withdraw_callback(
+                bob(),
+                U128(ATTACHED_DEPOSIT),
+                U128(ATTACHED_DEPOSIT),
+                None,
+                None,
+                None
+            ),
+            "Withdrawal should fail"
+        );
+
+        assert_eq!(
+            ATTACHED_DEPOSIT,
+            contract.deposits_of(&bob()),
+            "Balance should be restored"
+        );
+
+        assert_eq!(
+            ATTACHED_DEPOSIT,
+            contract.get_total_funds(),
+            "Total funds should be restored"
+        );
+
+        assert_eq!(
+            MIN_FUNDING_AMOUNT - ATTACHED_DEPOSIT,
+            contract.get_unpaid_funding_amount(),
+            "Unpaid funding amount should be restored"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INSUFFICIENT_ATTACHED_DEPOSIT")]
+    fn test_ft_delegate_funds_without_attached_deposit() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                funding_token_id: Some(accounts(5)),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+
+        contract.ft_on_transfer(bob(), U128(MIN_FUNDING_AMOUNT), "".to_string());
+
+        testing_env!(context
+            .predecessor_account_id(alice())
+            .block_timestamp(expires_at + 200)
+            .attached_deposit(FT_ATTACHED_DEPOSIT)
+            .build());
+
+        contract.delegate_funds("dao1".to_string());
+    }
+
+    #[test]
+    fn test_ft_delegate_funds() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                funding_token_id: Some(accounts(5)),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+
+        contract.ft_on_transfer(bob(), U128(MIN_FUNDING_AMOUNT / 2), "".to_string());
+        contract.ft_on_transfer(carol(), U128(MIN_FUNDING_AMOUNT / 2), "".to_string());
+
+        testing_env!(context
+            .predecessor_account_id(alice())
+            .block_timestamp(expires_at + 200)
+            .attached_deposit(FT_ATTACHED_DEPOSIT * 2)
+            .build());
+
+        contract.delegate_funds("dao1".to_string());
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful("true".to_string().into_bytes()),
+                PromiseResult::Successful("true".to_string().into_bytes())
+            ],
+        );
+
+        assert!(
+            contract.on_delegate_callback("dao1".to_string()),
+            "delegate_funds should run successfully"
+        );
+
+        assert_eq!(0, contract.get_total_funds(), "Total funds should be 0");
+
+        assert_eq!(
             0,
-            contract.deposits_of(&alice()),
-            "Account deposits should be 0"
+            contract.get_pending_dao_funds(),
+            "Funds should be forwarded to the DAO"
+        );
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(
+                "\"dao1.sputnikv2.testnet\"".to_string().into_bytes()
+            )],
+        );
+
+        contract.on_get_dao_account_callback(U128(MIN_FUNDING_AMOUNT));
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+
+        assert!(
+            !contract.on_forward_funds_callback(
+                "dao1.sputnikv2.testnet".parse().unwrap(),
+                U128(MIN_FUNDING_AMOUNT)
+            ),
+            "Forwarding the funds should fail"
+        );
+
+        assert_eq!(
+            MIN_FUNDING_AMOUNT,
+            contract.get_pending_dao_funds(),
+            "Funds should be pending to be forwarded again"
+        );
+
+        contract.forward_funds();
+
+        assert_eq!(0, contract.get_pending_dao_funds());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_FEE_BASIS_POINTS")]
+    fn test_new_invalid_fee_basis_points() {
+        setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                fee_basis_points: Some(MAX_FEE_BASIS_POINTS + 1),
+                fee_account_id: Some("fees.near".parse().unwrap()),
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_FEE_ACCOUNT_ID_REQUIRED")]
+    fn test_new_fee_account_id_required() {
+        setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                fee_basis_points: Some(FEE_BASIS_POINTS),
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    fn test_delegate_funds_charges_fee() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                fee_basis_points: Some(FEE_BASIS_POINTS),
+                fee_account_id: Some("fees.near".parse().unwrap()),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(FEE_BASIS_POINTS, contract.get_fee_basis_points());
+        assert_eq!(
+            Some("fees.near".parse().unwrap()),
+            contract.get_fee_account_id()
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.block_timestamp(expires_at + 200).build());
+
+        contract.delegate_funds("dao1".to_string());
+
+        let fee_amount = MIN_FUNDING_AMOUNT * FEE_BASIS_POINTS as u128 / 10_000;
+
+        assert_eq!(
+            fee_amount,
+            contract.get_fee_amount(),
+            "Fee should be reserved"
+        );
+
+        let event = EscrowEvent::DelegateStarted(DelegateStartedEvent {
+            dao_name: "dao1".to_string(),
+            dao_amount: U128(MIN_FUNDING_AMOUNT - FT_ATTACHED_DEPOSIT - fee_amount),
+            ft_amount: U128(FT_ATTACHED_DEPOSIT),
+            fee_amount: U128(fee_amount),
+            depositors_count: 1,
+        });
+
+        assert!(
+            get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())),
+            "The fee should be deducted from the DAO funds"
+        );
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful("true".to_string().into_bytes()),
+                PromiseResult::Successful("true".to_string().into_bytes())
+            ],
+        );
+
+        assert!(contract.on_delegate_callback("dao1".to_string()));
+
+        assert_eq!(
+            fee_amount,
+            contract.get_fee_amount(),
+            "Fee should be charged"
+        );
+    }
+
+    #[test]
+    fn test_failed_fee_payout_is_claimable() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                fee_basis_points: Some(FEE_BASIS_POINTS),
+                fee_account_id: Some("fees.near".parse().unwrap()),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.block_timestamp(expires_at + 200).build());
+
+        contract.delegate_funds("dao1".to_string());
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful("true".to_string().into_bytes()),
+                PromiseResult::Successful("true".to_string().into_bytes())
+            ],
+        );
+
+        assert!(contract.on_delegate_callback("dao1".to_string()));
+        assert!(get_function_call_gas(Some("on_payout_callback")) > 0);
+
+        let fee_account_id: AccountId = "fees.near".parse().unwrap();
+        let fee_amount = contract.get_fee_amount();
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+
+        assert!(!contract.on_payout_callback(fee_account_id.clone(), U128(fee_amount)));
+        assert_eq!(fee_amount, contract.get_pending_payout_of(&fee_account_id));
+
+        testing_env!(context
+            .predecessor_account_id(fee_account_id.clone())
+            .build());
+
+        contract.withdraw_payout();
+
+        assert_eq!(0, contract.get_pending_payout_of(&fee_account_id));
+        assert!(get_function_call_gas(Some("on_payout_callback")) > 0);
+    }
+
+    #[test]
+    fn test_delegate_funds_refunds_fee_on_failure() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                fee_basis_points: Some(FEE_BASIS_POINTS),
+                fee_account_id: Some("fees.near".parse().unwrap()),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.block_timestamp(expires_at + 200).build());
+
+        contract.delegate_funds("dao1".to_string());
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful("false".to_string().into_bytes()),
+                PromiseResult::Successful("true".to_string().into_bytes())
+            ],
+        );
+
+        assert!(!contract.on_delegate_callback("dao1".to_string()));
+
+        assert_eq!(0, contract.get_fee_amount(), "Fee should be refunded");
+
+        assert_eq!(
+            MIN_FUNDING_AMOUNT,
+            contract.get_total_funds(),
+            "Total funds should be MIN_FUNDING_AMOUNT"
+        );
+    }
+
+    #[test]
+    fn test_get_status() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        assert_eq!(CampaignStatus::Funding, contract.get_status());
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT)
+            .build());
+
+        contract.deposit();
+
+        assert_eq!(CampaignStatus::Funded, contract.get_status());
+
+        testing_env!(context.block_timestamp(expires_at + 200).build());
+
+        assert_eq!(
+            CampaignStatus::Funded,
+            contract.get_status(),
+            "A funded campaign should not expire"
+        );
+
+        contract.delegate_funds("dao1".to_string());
+
+        assert_eq!(CampaignStatus::Delegating, contract.get_status());
+
+        let event = EscrowEvent::StatusChanged(StatusChangedEvent {
+            previous_status: CampaignStatus::Funded,
+            status: CampaignStatus::Delegating,
+        });
+
+        assert!(
+            get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())),
+            "Should emit a status_changed event"
+        );
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful("true".to_string().into_bytes()),
+                PromiseResult::Successful("true".to_string().into_bytes())
+            ],
+        );
+
+        contract.on_delegate_callback("dao1".to_string());
+
+        assert_eq!(CampaignStatus::Delegated, contract.get_status());
+    }
+
+    #[test]
+    fn test_get_status_expired() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.block_timestamp(expires_at + 200).build());
+
+        assert_eq!(CampaignStatus::Expired, contract.get_status());
+
+        contract.withdraw();
+
+        assert_eq!(
+            CampaignStatus::Expired,
+            contract.status,
+            "Should be persisted"
+        );
+    }
+
+    #[test]
+    fn test_get_status_delegation_failed() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT)
+            .build());
+
+        contract.deposit();
+
+        contract.delegate_funds("dao1".to_string());
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful("false".to_string().into_bytes()),
+                PromiseResult::Successful("true".to_string().into_bytes())
+            ],
+        );
+
+        contract.on_delegate_callback("dao1".to_string());
+
+        assert_eq!(CampaignStatus::DelegationFailed, contract.get_status());
+        assert!(!contract.is_deposit_allowed());
+        assert!(!contract.is_withdrawal_allowed());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_WITHDRAWAL_NOT_ALLOWED")]
+    fn test_should_not_withdraw_if_delegated() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.block_timestamp(expires_at + 200).build());
+
+        contract.delegate_funds("dao1".to_string());
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful("true".to_string().into_bytes()),
+                PromiseResult::Successful("true".to_string().into_bytes())
+            ],
+        );
+
+        contract.on_delegate_callback("dao1".to_string());
+
+        assert_eq!(0, contract.get_total_funds(), "Total funds should be 0");
+
+        contract.withdraw();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DELEGATE_NOT_IN_PROGRESS")]
+    fn test_on_delegate_callback_not_delegating() {
+        let context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful("true".to_string().into_bytes()),
+                PromiseResult::Successful("true".to_string().into_bytes())
+            ],
+        );
+
+        contract.on_delegate_callback("dao1".to_string());
+    }
+
+    #[test]
+    fn test_campaign_status_transitions() {
+        use CampaignStatus::*;
+
+        assert!(Funding.can_transition_to(Funded));
+        assert!(Funding.can_transition_to(Expired));
+        assert!(Funded.can_transition_to(Delegating));
+        assert!(Delegating.can_transition_to(Delegated));
+        assert!(Delegating.can_transition_to(DelegationFailed));
+        assert!(Funded.can_transition_to(Cancelled));
+        assert!(DelegationFailed.can_transition_to(Cancelled));
+
+        assert!(Funded.can_transition_to(Funding));
+        assert!(!Expired.can_transition_to(Funding));
+        assert!(!Expired.can_transition_to(Delegating));
+        assert!(!Delegated.can_transition_to(Delegating));
+        assert!(!Cancelled.can_transition_to(Funding));
+        assert!(!Delegated.can_transition_to(Cancelled));
+    }
+
+    fn setup_failed_delegation(
+        context: &mut VMContextBuilder,
+        expires_at: u64,
+        dao_result: PromiseResult,
+        ft_result: PromiseResult,
+    ) -> ConditionalEscrow {
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT / 2)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(MIN_FUNDING_AMOUNT / 2)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .attached_deposit(0)
+            .block_timestamp(expires_at + 200)
+            .build());
+
+        contract.delegate_funds("dao1".to_string());
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![dao_result, ft_result],
+        );
+
+        assert!(
+            !contract.on_delegate_callback("dao1".to_string()),
+            "delegate_funds should fail"
+        );
+
+        assert_eq!(CampaignStatus::DelegationFailed, contract.get_status());
+        assert_eq!(expires_at + 200, contract.get_delegation_failed_at());
+
+        contract
+    }
+
+    #[test]
+    fn test_delegate_funds_retry_after_create_ft_fails() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_failed_delegation(
+            &mut context,
+            expires_at,
+            PromiseResult::Successful("true".to_string().into_bytes()),
+            PromiseResult::Failed,
+        );
+
+        assert!(contract.is_dao_created);
+        assert!(!contract.is_ft_created());
+        assert_eq!("dao1", contract.get_dao_name());
+        assert_eq!(0, contract.get_total_funds(), "Funds are in the DAO");
+        assert_eq!(0, contract.get_delegation_costs());
+
+        // Funds are in the DAO, only the FT creation can be retried
+        testing_env!(context
+            .block_timestamp(expires_at + 200 + DELEGATION_RETRY_PERIOD + 1)
+            .build());
+
+        assert!(!contract.is_withdrawal_allowed());
+        assert!(contract.is_delegation_allowed());
+
+        contract.delegate_funds("dao1".to_string());
+
+        let event = EscrowEvent::DelegateStarted(DelegateStartedEvent {
+            dao_name: "dao1".to_string(),
+            dao_amount: U128(0),
+            ft_amount: U128(FT_ATTACHED_DEPOSIT),
+            fee_amount: U128(0),
+            depositors_count: 2,
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful("true".to_string().into_bytes())],
+        );
+
+        assert!(
+            contract.on_delegate_callback("dao1".to_string()),
+            "delegate_funds retry should run successfully"
+        );
+
+        assert_eq!(CampaignStatus::Delegated, contract.get_status());
+        assert_eq!("dao1", contract.get_dao_name());
+    }
+
+    #[test]
+    fn test_delegation_fails_when_the_callback_times_out() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                fee_basis_points: Some(FEE_BASIS_POINTS),
+                fee_account_id: Some("fees.near".parse().unwrap()),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT)
+            .build());
+
+        contract.deposit();
+
+        let delegated_at = expires_at + 200;
+
+        testing_env!(context
+            .attached_deposit(0)
+            .block_timestamp(delegated_at)
+            .build());
+
+        contract.delegate_funds("dao1".to_string());
+
+        assert!(contract.fee_amount > 0);
+
+        // on_delegate_callback never completes
+        testing_env!(context
+            .block_timestamp(delegated_at + DELEGATION_CALLBACK_TIMEOUT)
+            .build());
+
+        assert_eq!(CampaignStatus::Delegating, contract.get_status());
+        assert!(!contract.is_delegation_allowed());
+
+        let failed_at = delegated_at + DELEGATION_CALLBACK_TIMEOUT;
+
+        testing_env!(context.block_timestamp(failed_at + 1).build());
+
+        assert_eq!(CampaignStatus::DelegationFailed, contract.get_status());
+        assert_eq!(failed_at, contract.get_delegation_failed_at());
+        assert!(contract.is_delegation_allowed());
+        assert!(!contract.is_withdrawal_allowed());
+
+        // The retry persists the failure and reserves the fee again
+        contract.delegate_funds("dao2".to_string());
+
+        assert_eq!(CampaignStatus::Delegating, contract.get_status());
+        assert_eq!(failed_at, contract.delegation_failed_at);
+        assert!(contract.fee_amount > 0);
+
+        // Refunds open once the retry period of the second timeout ended
+        let failed_at = failed_at + 1 + DELEGATION_CALLBACK_TIMEOUT;
+
+        testing_env!(context
+            .block_timestamp(failed_at + DELEGATION_RETRY_PERIOD + 1)
+            .build());
+
+        assert_eq!(CampaignStatus::DelegationFailed, contract.get_status());
+        assert!(contract.is_withdrawal_allowed());
+        assert!(!contract.is_delegation_allowed());
+
+        contract.withdraw();
+
+        assert_eq!(CampaignStatus::DelegationFailed, contract.status);
+        assert_eq!(failed_at, contract.delegation_failed_at);
+        assert_eq!(0, contract.fee_amount);
+        assert_eq!(0, contract.deposits_of(&bob()));
+    }
+
+    #[test]
+    fn test_retry_create_ft_keeps_the_dao_name() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_failed_delegation(
+            &mut context,
+            expires_at,
+            PromiseResult::Successful("true".to_string().into_bytes()),
+            PromiseResult::Failed,
+        );
+
+        contract.delegate_funds("dao2".to_string());
+
+        let event = EscrowEvent::DelegateStarted(DelegateStartedEvent {
+            dao_name: "dao1".to_string(),
+            dao_amount: U128(0),
+            ft_amount: U128(FT_ATTACHED_DEPOSIT),
+            fee_amount: U128(0),
+            depositors_count: 2,
+        });
+
+        assert!(
+            get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())),
+            "The FT should be created under the name of the DAO"
+        );
+    }
+
+    #[test]
+    fn test_delegate_funds_retry_after_create_dao_fails() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_failed_delegation(
+            &mut context,
+            expires_at,
+            PromiseResult::Failed,
+            PromiseResult::Successful("true".to_string().into_bytes()),
+        );
+
+        assert!(!contract.is_dao_created);
+        assert!(contract.is_ft_created());
+        assert_eq!(MIN_FUNDING_AMOUNT, contract.get_total_funds());
+        assert_eq!(FT_ATTACHED_DEPOSIT, contract.get_delegation_costs());
+
+        assert!(!contract.is_withdrawal_allowed());
+        assert!(contract.is_delegation_allowed());
+
+        // Retry the DAO creation with a new name
+        contract.delegate_funds("dao2".to_string());
+
+        let event = EscrowEvent::DelegateStarted(DelegateStartedEvent {
+            dao_name: "dao2".to_string(),
+            dao_amount: U128(MIN_FUNDING_AMOUNT - FT_ATTACHED_DEPOSIT),
+            ft_amount: U128(0),
+            fee_amount: U128(0),
+            depositors_count: 2,
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful("true".to_string().into_bytes())],
+        );
+
+        assert!(
+            contract.on_delegate_callback("dao2".to_string()),
+            "delegate_funds retry should run successfully"
+        );
+
+        assert_eq!(CampaignStatus::Delegated, contract.get_status());
+        assert_eq!("dao2", contract.get_dao_name());
+        assert_eq!(0, contract.get_total_funds());
+        assert_eq!(0, contract.get_delegation_costs());
+    }
+
+    #[test]
+    fn test_withdraw_after_delegation_retry_period() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_failed_delegation(
+            &mut context,
+            expires_at,
+            PromiseResult::Successful("false".to_string().into_bytes()),
+            PromiseResult::Successful("true".to_string().into_bytes()),
+        );
+
+        testing_env!(context
+            .block_timestamp(expires_at + 200 + DELEGATION_RETRY_PERIOD + 1)
+            .predecessor_account_id(bob())
+            .build());
+
+        assert!(contract.is_withdrawal_allowed());
+        assert!(!contract.is_delegation_allowed());
+
+        // The FT creation deposit is deducted pro-rata
+        contract.withdraw();
+
+        let event = EscrowEvent::Withdraw(WithdrawEvent {
+            account_id: bob(),
+            amount: U128((MIN_FUNDING_AMOUNT - FT_ATTACHED_DEPOSIT) / 2),
+            balance: U128(0),
+            total_funds: U128(MIN_FUNDING_AMOUNT / 2),
+            unpaid_funding_amount: U128(MIN_FUNDING_AMOUNT / 2),
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        testing_env!(context.predecessor_account_id(carol()).build());
+
+        contract.withdraw();
+
+        let event = EscrowEvent::Withdraw(WithdrawEvent {
+            account_id: carol(),
+            amount: U128((MIN_FUNDING_AMOUNT - FT_ATTACHED_DEPOSIT) / 2),
+            balance: U128(0),
+            total_funds: U128(0),
+            unpaid_funding_amount: U128(MIN_FUNDING_AMOUNT),
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        assert_eq!(0, contract.get_total_funds());
+        assert_eq!(0, contract.get_delegation_costs());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DELEGATE_NOT_ALLOWED")]
+    fn test_should_not_delegate_funds_after_delegation_retry_period() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_failed_delegation(
+            &mut context,
+            expires_at,
+            PromiseResult::Failed,
+            PromiseResult::Failed,
+        );
+
+        assert_eq!(0, contract.get_delegation_costs());
+
+        testing_env!(context
+            .block_timestamp(expires_at + 200 + DELEGATION_RETRY_PERIOD + 1)
+            .build());
+
+        contract.delegate_funds("dao2".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DELEGATION_REFUNDS_PENDING")]
+    fn test_should_not_retry_delegation_before_refunds() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_failed_delegation(
+            &mut context,
+            expires_at,
+            PromiseResult::Failed,
+            PromiseResult::Failed,
+        );
+
+        testing_env!(context.account_balance(FT_ATTACHED_DEPOSIT).build());
+
+        contract.delegate_funds("dao2".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_SOFT_CAP_EXCEEDS_FUNDS_LIMIT")]
+    fn test_new_soft_cap_exceeds_funds_limit() {
+        setup_context();
+
+        setup_contract_with(
+            add_expires_at_nanos(100),
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                soft_cap: Some(U128(MIN_FUNDING_AMOUNT + 1)),
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INSUFFICIENT_FUNDS_LIMIT")]
+    fn test_new_insufficient_soft_cap() {
+        setup_context();
+
+        setup_contract_with(
+            add_expires_at_nanos(100),
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                soft_cap: Some(U128(FT_ATTACHED_DEPOSIT - 1)),
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    fn test_soft_cap_reached() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                soft_cap: Some(U128(MIN_FUNDING_AMOUNT / 3)),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(MIN_FUNDING_AMOUNT / 3, contract.get_soft_cap());
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT / 3)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(MIN_FUNDING_AMOUNT / 6)
+            .build());
+
+        contract.deposit();
+
+        // Deposits continue until the hard cap or expires_at
+        assert_eq!(CampaignStatus::Funding, contract.get_status());
+        assert!(contract.is_deposit_allowed());
+        assert!(!contract.is_delegation_allowed());
+
+        testing_env!(context
+            .attached_deposit(0)
+            .block_timestamp(expires_at + 200)
+            .build());
+
+        assert_eq!(CampaignStatus::Funded, contract.get_status());
+        assert!(!contract.is_deposit_allowed());
+        assert!(!contract.is_withdrawal_allowed());
+        assert!(contract.is_delegation_allowed());
+
+        // Shares are computed against the raised funds
+        assert_eq!(MIN_FUNDING_AMOUNT / 2, contract.get_raised_funds());
+        assert_eq!(666, contract.get_shares_of(&bob()));
+        assert_eq!(333, contract.get_shares_of(&carol()));
+
+        contract.delegate_funds("dao1".to_string());
+
+        let event = EscrowEvent::DelegateStarted(DelegateStartedEvent {
+            dao_name: "dao1".to_string(),
+            dao_amount: U128(MIN_FUNDING_AMOUNT / 2 - FT_ATTACHED_DEPOSIT),
+            ft_amount: U128(FT_ATTACHED_DEPOSIT),
+            fee_amount: U128(0),
+            depositors_count: 2,
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful("true".to_string().into_bytes()),
+                PromiseResult::Successful("true".to_string().into_bytes())
+            ],
+        );
+
+        assert!(contract.on_delegate_callback("dao1".to_string()));
+
+        // Shares are kept after the funds are delegated
+        assert_eq!(0, contract.get_total_funds());
+        assert_eq!(666, contract.get_shares_of(&bob()));
+    }
+
+    #[test]
+    fn test_soft_cap_not_reached() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                soft_cap: Some(U128(MIN_FUNDING_AMOUNT / 3)),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT / 3 - 1)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .attached_deposit(0)
+            .block_timestamp(expires_at + 200)
+            .build());
+
+        assert_eq!(CampaignStatus::Expired, contract.get_status());
+        assert!(contract.is_withdrawal_allowed());
+        assert!(!contract.is_delegation_allowed());
+
+        contract.withdraw();
+
+        assert_eq!(0, contract.get_total_funds());
+        assert_eq!(0, contract.get_raised_funds());
+        assert_eq!(0, contract.get_shares_of(&bob()));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DEPOSIT_NOT_ALLOWED")]
+    fn test_hard_cap_stops_deposits() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                soft_cap: Some(U128(MIN_FUNDING_AMOUNT / 3)),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT)
+            .build());
+
+        contract.deposit();
+
+        // Funded before expires_at
+        assert_eq!(CampaignStatus::Funded, contract.get_status());
+        assert!(contract.is_delegation_allowed());
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+    }
+
+    #[test]
+    fn test_oversubscription() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                is_oversubscription_allowed: Some(true),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 20)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT * 10)
+            .build());
+
+        contract.deposit();
+
+        // Deposits continue over the funding_amount_limit until expires_at
+        assert_eq!(CampaignStatus::Funding, contract.get_status());
+        assert!(contract.is_deposit_allowed());
+        assert!(!contract.is_excess_withdrawal_allowed());
+        assert_eq!(ATTACHED_DEPOSIT * 30, contract.get_raised_funds());
+        assert_eq!(MIN_FUNDING_AMOUNT, contract.get_allocated_funds());
+        assert_eq!(0, contract.get_unpaid_funding_amount());
+
+        testing_env!(context
+            .attached_deposit(0)
+            .block_timestamp(expires_at + 200)
+            .build());
+
+        assert_eq!(CampaignStatus::Funded, contract.get_status());
+        assert!(contract.is_excess_withdrawal_allowed());
+
+        assert_eq!(ATTACHED_DEPOSIT * 10, contract.get_allocation_of(&bob()));
+        assert_eq!(ATTACHED_DEPOSIT * 10, contract.get_excess_of(&bob()));
+        assert_eq!(ATTACHED_DEPOSIT * 5, contract.get_allocation_of(&carol()));
+        assert_eq!(ATTACHED_DEPOSIT * 5, contract.get_excess_of(&carol()));
+        assert_eq!(666, contract.get_shares_of(&bob()));
+        assert_eq!(333, contract.get_shares_of(&carol()));
+
+        testing_env!(context.predecessor_account_id(bob()).build());
+
+        contract.withdraw_excess();
+
+        let event = EscrowEvent::Withdraw(WithdrawEvent {
+            account_id: bob(),
+            amount: U128(ATTACHED_DEPOSIT * 10),
+            balance: U128(ATTACHED_DEPOSIT * 10),
+            total_funds: U128(ATTACHED_DEPOSIT * 20),
+            unpaid_funding_amount: U128(0),
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        assert_eq!(0, contract.get_excess_of(&bob()));
+        assert_eq!(ATTACHED_DEPOSIT * 10, contract.get_allocation_of(&bob()));
+
+        // Only the allocations are delegated
+        contract.delegate_funds("dao1".to_string());
+
+        let event = EscrowEvent::DelegateStarted(DelegateStartedEvent {
+            dao_name: "dao1".to_string(),
+            dao_amount: U128(MIN_FUNDING_AMOUNT - FT_ATTACHED_DEPOSIT),
+            ft_amount: U128(FT_ATTACHED_DEPOSIT),
+            fee_amount: U128(0),
+            depositors_count: 2,
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful("true".to_string().into_bytes()),
+                PromiseResult::Successful("true".to_string().into_bytes())
+            ],
+        );
+
+        assert!(contract.on_delegate_callback("dao1".to_string()));
+
+        // Carol's excess stays in the escrow
+        assert_eq!(ATTACHED_DEPOSIT * 5, contract.get_total_funds());
+
+        testing_env!(context.predecessor_account_id(carol()).build());
+
+        assert!(contract.is_excess_withdrawal_allowed());
+
+        contract.withdraw_excess();
+
+        assert_eq!(0, contract.get_total_funds());
+        assert_eq!(333, contract.get_shares_of(&carol()));
+    }
+
+    #[test]
+    fn test_oversubscription_excess_dust_joins_dao_funds() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                is_oversubscription_allowed: Some(true),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 10)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT * 10 + 1)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .attached_deposit(0)
+            .block_timestamp(expires_at + 200)
+            .build());
+
+        // The excess of each depositor is rounded down, 1 yocto of the raised funds is left out
+        let excess = contract.get_excess_of(&bob()) + contract.get_excess_of(&carol());
+        assert_eq!(ATTACHED_DEPOSIT * 5, excess);
+
+        contract.delegate_funds("dao1".to_string());
+
+        let event = EscrowEvent::DelegateStarted(DelegateStartedEvent {
+            dao_name: "dao1".to_string(),
+            dao_amount: U128(MIN_FUNDING_AMOUNT - FT_ATTACHED_DEPOSIT + 1),
+            ft_amount: U128(FT_ATTACHED_DEPOSIT),
+            fee_amount: U128(0),
+            depositors_count: 2,
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful("true".to_string().into_bytes()),
+                PromiseResult::Successful("true".to_string().into_bytes())
+            ],
+        );
+
+        assert!(contract.on_delegate_callback("dao1".to_string()));
+
+        // Only the excess of the depositors stays in the escrow
+        assert_eq!(excess, contract.get_total_funds());
+
+        contract.withdraw_excess();
+
+        testing_env!(context.predecessor_account_id(bob()).build());
+
+        contract.withdraw_excess();
+
+        assert_eq!(0, contract.get_total_funds());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_WITHDRAWAL_NOT_ALLOWED")]
+    fn test_should_not_withdraw_excess_while_funding() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                is_oversubscription_allowed: Some(true),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 20)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT * 10)
+            .build());
+
+        contract.deposit();
+
+        contract.withdraw_excess();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NO_EXCESS_TO_WITHDRAW")]
+    fn test_should_not_withdraw_excess_twice() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                is_oversubscription_allowed: Some(true),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 20)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT * 10)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .attached_deposit(0)
+            .block_timestamp(expires_at + 200)
+            .build());
+
+        contract.withdraw_excess();
+        contract.withdraw_excess();
+    }
+
+    #[test]
+    fn test_oversubscription_withdraw_after_delegation_fails() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                is_oversubscription_allowed: Some(true),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 20)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT * 10)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .attached_deposit(0)
+            .block_timestamp(expires_at + 200)
+            .build());
+
+        contract.withdraw_excess();
+
+        contract.delegate_funds("dao1".to_string());
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed, PromiseResult::Failed],
+        );
+
+        assert!(!contract.on_delegate_callback("dao1".to_string()));
+
+        testing_env!(context
+            .block_timestamp(expires_at + 200 + DELEGATION_RETRY_PERIOD + 1)
+            .build());
+
+        assert!(contract.is_withdrawal_allowed());
+        assert!(!contract.is_excess_withdrawal_allowed());
+
+        // Carol withdrew her excess already, only her allocation is refunded
+        contract.withdraw();
+
+        let event = EscrowEvent::Withdraw(WithdrawEvent {
+            account_id: carol(),
+            amount: U128(ATTACHED_DEPOSIT * 5),
+            balance: U128(0),
+            total_funds: U128(ATTACHED_DEPOSIT * 20),
+            unpaid_funding_amount: U128(0),
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        testing_env!(context.predecessor_account_id(bob()).build());
+
+        contract.withdraw();
+
+        assert_eq!(0, contract.get_total_funds());
+        assert_eq!(0, contract.get_raised_funds());
+        assert_eq!(MIN_FUNDING_AMOUNT, contract.get_unpaid_funding_amount());
+    }
+
+    #[test]
+    fn test_deposit_refunds_surplus() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT - ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT * 3)
+            .build());
+
+        contract.deposit();
+
+        assert!(get_logs().contains(&format!(
+            "{} refunded {} NEAR tokens over the funding amount limit",
+            carol(),
+            ATTACHED_DEPOSIT * 2
+        )));
+
+        let event = EscrowEvent::Deposit(DepositEvent {
+            account_id: carol(),
+            amount: U128(ATTACHED_DEPOSIT),
+            balance: U128(ATTACHED_DEPOSIT),
+            total_funds: U128(MIN_FUNDING_AMOUNT),
+            unpaid_funding_amount: U128(0),
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        assert_eq!(ATTACHED_DEPOSIT, contract.deposits_of(&carol()));
+        assert_eq!(MIN_FUNDING_AMOUNT, contract.get_total_funds());
+        assert_eq!(CampaignStatus::Funded, contract.get_status());
+    }
+
+    #[test]
+    fn test_ft_on_transfer_returns_surplus() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                funding_token_id: Some(accounts(5)),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+
+        match contract.ft_on_transfer(bob(), U128(MIN_FUNDING_AMOUNT + 1), "".to_string()) {
+            PromiseOrValue::Value(unused_amount) => {
+                assert_eq!(1, unused_amount.0, "The surplus should be returned")
+            }
+            _ => panic!("ft_on_transfer should return a value"),
+        }
+
+        assert_eq!(MIN_FUNDING_AMOUNT, contract.deposits_of(&bob()));
+        assert_eq!(CampaignStatus::Funded, contract.get_status());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MIN_DEPOSIT_EXCEEDS_FUNDS_LIMIT")]
+    fn test_new_min_deposit_exceeds_funds_limit() {
+        setup_context();
+
+        setup_contract_with(
+            add_expires_at_nanos(100),
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                min_deposit: Some(U128(MIN_FUNDING_AMOUNT + 1)),
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_MAX_DEPOSIT_PER_ACCOUNT")]
+    fn test_new_max_deposit_exceeds_funds_limit() {
+        setup_context();
+
+        setup_contract_with(
+            add_expires_at_nanos(100),
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                max_deposit_per_account: Some(U128(MIN_FUNDING_AMOUNT + 1)),
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MIN_DEPOSIT_EXCEEDS_MAX_DEPOSIT")]
+    fn test_new_min_deposit_exceeds_max_deposit() {
+        setup_context();
+
+        setup_contract_with(
+            add_expires_at_nanos(100),
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                min_deposit: Some(U128(ATTACHED_DEPOSIT * 2)),
+                max_deposit_per_account: Some(U128(ATTACHED_DEPOSIT)),
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    fn test_deposit_limits() {
+        let mut context = setup_context();
+
+        let mut contract = setup_contract_with(
+            add_expires_at_nanos(100),
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                min_deposit: Some(U128(ATTACHED_DEPOSIT * 2)),
+                max_deposit_per_account: Some(U128(ATTACHED_DEPOSIT * 10)),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(ATTACHED_DEPOSIT * 2, contract.get_min_deposit());
+        assert_eq!(
+            Some(ATTACHED_DEPOSIT * 10),
+            contract.get_max_deposit_per_account()
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 2)
+            .build());
+
+        contract.deposit();
+
+        // The minimum applies to the balance, not to each deposit
+        testing_env!(context.attached_deposit(ATTACHED_DEPOSIT).build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(ATTACHED_DEPOSIT * 7).build());
+
+        contract.deposit();
+
+        assert_eq!(ATTACHED_DEPOSIT * 10, contract.deposits_of(&bob()));
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT * 4)
+            .build());
+
+        contract.deposit();
+
+        // The last deposit completes the funding below the minimum
+        testing_env!(context
+            .predecessor_account_id(accounts(5))
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+
+        assert_eq!(CampaignStatus::Funded, contract.get_status());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MIN_DEPOSIT_NOT_REACHED")]
+    fn test_deposit_below_min_deposit() {
+        let mut context = setup_context();
+
+        let mut contract = setup_contract_with(
+            add_expires_at_nanos(100),
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                min_deposit: Some(U128(ATTACHED_DEPOSIT * 2)),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MAX_DEPOSIT_PER_ACCOUNT_EXCEEDED")]
+    fn test_deposit_over_max_deposit_per_account() {
+        let mut context = setup_context();
+
+        let mut contract = setup_contract_with(
+            add_expires_at_nanos(100),
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                max_deposit_per_account: Some(U128(ATTACHED_DEPOSIT * 2)),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 2)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(1).build());
+
+        contract.deposit();
+    }
+
+    #[test]
+    fn test_deposit_for() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        // A treasury contract deposits on behalf of bob
+        testing_env!(context
+            .predecessor_account_id(accounts(5))
+            .signer_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit_for(bob());
+
+        assert_eq!(ATTACHED_DEPOSIT, contract.deposits_of(&bob()));
+        assert_eq!(0, contract.deposits_of(&accounts(5)));
+        assert_eq!(0, contract.deposits_of(&carol()));
+
+        // Balances are keyed on the predecessor, not the signer
+        contract.deposit();
+
+        assert_eq!(ATTACHED_DEPOSIT, contract.deposits_of(&accounts(5)));
+        assert_eq!(0, contract.deposits_of(&carol()));
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(0)
+            .block_timestamp(expires_at + 200)
+            .build());
+
+        contract.withdraw();
+
+        let event = EscrowEvent::Withdraw(WithdrawEvent {
+            account_id: bob(),
+            amount: U128(ATTACHED_DEPOSIT),
+            balance: U128(0),
+            total_funds: U128(ATTACHED_DEPOSIT),
+            unpaid_funding_amount: U128(MIN_FUNDING_AMOUNT - ATTACHED_DEPOSIT),
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_OWNER_SHOULD_NOT_DEPOSIT")]
+    fn test_owner_deposit_for() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit_for(alice());
+    }
+
+    #[test]
+    fn test_ft_on_transfer_for_beneficiary() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                funding_token_id: Some(accounts(5)),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+
+        contract.ft_on_transfer(bob(), U128(ATTACHED_DEPOSIT), carol().to_string());
+
+        assert_eq!(0, contract.deposits_of(&bob()));
+        assert_eq!(ATTACHED_DEPOSIT, contract.deposits_of(&carol()));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_BENEFICIARY")]
+    fn test_ft_on_transfer_invalid_beneficiary() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                funding_token_id: Some(accounts(5)),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+
+        contract.ft_on_transfer(bob(), U128(ATTACHED_DEPOSIT), "Not An Account".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ZERO_BALANCE")]
+    fn test_should_not_withdraw_zero_balance() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .block_timestamp(expires_at + 200)
+            .build());
+
+        contract.withdraw();
+    }
+
+    #[test]
+    fn test_withdraw_restores_balance_on_failure() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_failed_delegation(
+            &mut context,
+            expires_at,
+            PromiseResult::Failed,
+            PromiseResult::Successful("true".to_string().into_bytes()),
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .block_timestamp(expires_at + 200 + DELEGATION_RETRY_PERIOD + 1)
+            .build());
+
+        let payment = (MIN_FUNDING_AMOUNT - FT_ATTACHED_DEPOSIT) / 2;
+
+        contract.withdraw();
+
+        assert_eq!(0, contract.deposits_of(&bob()));
+        assert_eq!(FT_ATTACHED_DEPOSIT / 2, contract.get_delegation_costs());
+
+        testing_env!(
+            context.predecessor_account_id(alice()).build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+
+        assert!(!contract.on_withdraw_callback(
+            bob(),
+            U128(MIN_FUNDING_AMOUNT / 2),
+            U128(payment),
+            None,
+            None,
+            None
+        ));
+
+        let event = EscrowEvent::WithdrawFailed(WithdrawFailedEvent {
+            account_id: bob(),
+            amount: U128(payment),
+            balance: U128(MIN_FUNDING_AMOUNT / 2),
+            total_funds: U128(MIN_FUNDING_AMOUNT),
+            unpaid_funding_amount: U128(0),
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        assert_eq!(MIN_FUNDING_AMOUNT / 2, contract.deposits_of(&bob()));
+        assert_eq!(MIN_FUNDING_AMOUNT, contract.get_total_funds());
+        assert_eq!(MIN_FUNDING_AMOUNT, contract.get_raised_funds());
+        assert_eq!(FT_ATTACHED_DEPOSIT, contract.get_delegation_costs());
+
+        // The withdrawal can be retried
+        testing_env!(context.predecessor_account_id(bob()).build());
+
+        contract.withdraw();
+
+        let event = EscrowEvent::Withdraw(WithdrawEvent {
+            account_id: bob(),
+            amount: U128(payment),
+            balance: U128(0),
+            total_funds: U128(MIN_FUNDING_AMOUNT / 2),
+            unpaid_funding_amount: U128(MIN_FUNDING_AMOUNT / 2),
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+    }
+
+    #[test]
+    fn test_withdraw_callback_success() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .attached_deposit(0)
+            .block_timestamp(expires_at + 200)
+            .build());
+
+        contract.withdraw();
+
+        testing_env!(
+            context.predecessor_account_id(alice()).build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+
+        assert!(contract.on_withdraw_callback(
+            bob(),
+            U128(ATTACHED_DEPOSIT),
+            U128(ATTACHED_DEPOSIT),
+            None,
+            None,
+            None
+        ));
+
+        assert_eq!(0, contract.deposits_of(&bob()));
+        assert_eq!(0, contract.get_total_funds());
+    }
+
+    #[test]
+    fn test_withdraw_excess_restores_excess_on_failure() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                is_oversubscription_allowed: Some(true),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 20)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT * 10)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .attached_deposit(0)
+            .block_timestamp(expires_at + 200)
+            .build());
+
+        contract.withdraw_excess();
+
+        assert_eq!(0, contract.get_excess_of(&carol()));
+
+        testing_env!(
+            context.predecessor_account_id(alice()).build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+
+        assert!(!contract.on_withdraw_excess_callback(carol(), U128(ATTACHED_DEPOSIT * 5)));
+
+        assert_eq!(ATTACHED_DEPOSIT * 5, contract.get_excess_of(&carol()));
+        assert_eq!(ATTACHED_DEPOSIT * 30, contract.get_total_funds());
+    }
+
+    #[test]
+    fn test_get_deposits_paginated() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        for (index, account_id) in [bob(), carol(), accounts(5)].iter().enumerate() {
+            testing_env!(context
+                .predecessor_account_id(account_id.clone())
+                .attached_deposit(ATTACHED_DEPOSIT * (index as u128 + 1))
+                .build());
+
+            contract.deposit();
+        }
+
+        assert_eq!(3, contract.get_depositors_count());
+
+        assert_eq!(
+            vec![(bob(), ATTACHED_DEPOSIT), (carol(), ATTACHED_DEPOSIT * 2)],
+            contract.get_deposits_paginated(0, 2)
+        );
+
+        assert_eq!(
+            vec![(accounts(5), ATTACHED_DEPOSIT * 3)],
+            contract.get_deposits_paginated(2, 2)
+        );
+
+        assert!(contract.get_deposits_paginated(3, 2).is_empty());
+        assert_eq!(3, contract.get_deposits_paginated(0, u64::MAX).len());
+    }
+
+    #[test]
+    fn test_get_campaign_summary() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT * 3)
+            .block_timestamp(expires_at - 50)
+            .build());
+
+        contract.deposit();
+
+        let summary = contract.get_campaign_summary();
+
+        assert_eq!(CampaignStatus::Funding, summary.status);
+        assert_eq!(ATTACHED_DEPOSIT * 4, summary.total_funds.0);
+        assert_eq!(ATTACHED_DEPOSIT * 4, summary.raised_funds.0);
+        assert_eq!(MIN_FUNDING_AMOUNT, summary.funding_amount_limit.0);
+        assert_eq!(MIN_FUNDING_AMOUNT, summary.soft_cap.0);
+        assert_eq!(
+            MIN_FUNDING_AMOUNT - ATTACHED_DEPOSIT * 4,
+            summary.unpaid_funding_amount.0
+        );
+        assert_eq!(2, summary.depositors_count);
+        assert_eq!(Some(carol()), summary.largest_depositor);
+        assert_eq!(ATTACHED_DEPOSIT * 3, summary.largest_deposit.0);
+        assert_eq!(expires_at, summary.expires_at);
+        assert_eq!(50, summary.time_remaining);
+
+        testing_env!(context.block_timestamp(expires_at + 200).build());
+
+        let summary = contract.get_campaign_summary();
+
+        assert_eq!(CampaignStatus::Expired, summary.status);
+        assert_eq!(0, summary.time_remaining);
+
+        testing_env!(context.predecessor_account_id(carol()).build());
+
+        contract.withdraw();
+
+        let summary = contract.get_campaign_summary();
+
+        assert_eq!(1, contract.get_depositors_count());
+        assert_eq!(1, summary.depositors_count);
+        assert_eq!(Some(bob()), summary.largest_depositor);
+    }
+
+    #[test]
+    fn test_get_share_fraction_of() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                is_oversubscription_allowed: Some(true),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 20)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT * 10)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(5))
+            .attached_deposit(1)
+            .build());
+
+        contract.deposit();
+
+        // Below 0.1% of the funds
+        assert_eq!(0, contract.get_shares_of(&accounts(5)));
+
+        let share = contract.get_share_fraction_of(&accounts(5));
+
+        assert_eq!(1, share.numerator.0);
+        assert_eq!(ATTACHED_DEPOSIT * 30 + 1, share.denominator.0);
+
+        let share = contract.get_share_fraction_of(&bob());
+
+        assert_eq!(ATTACHED_DEPOSIT * 20, share.numerator.0);
+        assert_eq!(ATTACHED_DEPOSIT * 30 + 1, share.denominator.0);
+
+        let share = contract.get_share_fraction_of(&alice());
+
+        assert_eq!(0, share.numerator.0);
+    }
+
+    #[test]
+    fn test_transfer_position() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                is_position_transfer_approval_required: Some(false),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 3)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).build());
+
+        assert_eq!(accounts(5), contract.get_creator_account_id());
+        assert!(!contract.is_position_transfer_approval_required());
+
+        contract.transfer_position(carol(), U128(ATTACHED_DEPOSIT));
+
+        let event = EscrowEvent::PositionTransferred(PositionTransferredEvent {
+            sender_id: bob(),
+            receiver_id: carol(),
+            amount: U128(ATTACHED_DEPOSIT),
+            sender_balance: U128(ATTACHED_DEPOSIT * 2),
+            receiver_balance: U128(ATTACHED_DEPOSIT),
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        assert_eq!(ATTACHED_DEPOSIT * 2, contract.deposits_of(&bob()));
+        assert_eq!(ATTACHED_DEPOSIT, contract.deposits_of(&carol()));
+        assert_eq!(ATTACHED_DEPOSIT * 3, contract.get_total_funds());
+        assert_eq!(ATTACHED_DEPOSIT * 3, contract.get_raised_funds());
+
+        // The whole position
+        contract.transfer_position(carol(), U128(ATTACHED_DEPOSIT * 2));
+
+        assert_eq!(0, contract.deposits_of(&bob()));
+        assert_eq!(ATTACHED_DEPOSIT * 3, contract.deposits_of(&carol()));
+        assert_eq!(1000, contract.get_shares_of(&carol()));
+        assert_eq!(vec![carol().to_string()], contract.get_deposit_accounts());
+    }
+
+    #[test]
+    fn test_transfer_position_with_approval() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                is_position_transfer_approval_required: Some(true),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 3)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).build());
+
+        contract.transfer_position(carol(), U128(ATTACHED_DEPOSIT));
+
+        let event = EscrowEvent::PositionTransferRequested(PositionTransferRequestedEvent {
+            sender_id: bob(),
+            receiver_id: carol(),
+            amount: U128(ATTACHED_DEPOSIT),
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        assert_eq!(
+            Some((carol(), U128(ATTACHED_DEPOSIT))),
+            contract.get_pending_position_transfer(bob())
+        );
+        assert_eq!(ATTACHED_DEPOSIT * 3, contract.deposits_of(&bob()));
+
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+
+        contract.approve_position_transfer(bob());
+
+        assert_eq!(None, contract.get_pending_position_transfer(bob()));
+        assert_eq!(ATTACHED_DEPOSIT * 2, contract.deposits_of(&bob()));
+        assert_eq!(ATTACHED_DEPOSIT, contract.deposits_of(&carol()));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_CREATOR")]
+    fn test_approve_position_transfer_not_creator() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                is_position_transfer_approval_required: Some(true),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 3)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).build());
+
+        contract.transfer_position(carol(), U128(ATTACHED_DEPOSIT));
+        contract.approve_position_transfer(bob());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_POSITION_TRANSFER_NOT_ALLOWED")]
+    fn test_transfer_position_after_close() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                is_position_transfer_approval_required: Some(false),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 3)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).build());
+
+        testing_env!(context.block_timestamp(expires_at + 200).build());
+
+        contract.transfer_position(carol(), U128(ATTACHED_DEPOSIT));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_POSITION_AMOUNT")]
+    fn test_transfer_position_over_balance() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                is_position_transfer_approval_required: Some(false),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 3)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).build());
+
+        contract.transfer_position(carol(), U128(ATTACHED_DEPOSIT * 3 + 1));
+    }
+
+    #[test]
+    fn test_cancel() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(1_000_000);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                is_position_transfer_approval_required: Some(false),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 3)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).build());
+
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+
+        assert!(contract.is_cancel_allowed());
+
+        contract.cancel();
+
+        let event = EscrowEvent::StatusChanged(StatusChangedEvent {
+            previous_status: CampaignStatus::Funding,
+            status: CampaignStatus::Cancelled,
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        assert_eq!(CampaignStatus::Cancelled, contract.get_status());
+        assert!(!contract.is_deposit_allowed());
+        assert!(!contract.is_delegation_allowed());
+        assert!(!contract.is_cancel_allowed());
+
+        // Withdrawals are allowed before expires_at
+        testing_env!(context.predecessor_account_id(bob()).build());
+
+        assert!(contract.is_withdrawal_allowed());
+
+        contract.withdraw();
+
+        assert_eq!(0, contract.deposits_of(&bob()));
+        assert_eq!(0, contract.get_total_funds());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DEPOSIT_NOT_ALLOWED")]
+    fn test_should_not_deposit_if_cancelled() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(1_000_000);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                is_position_transfer_approval_required: Some(false),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 3)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).build());
+
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+
+        contract.cancel();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DELEGATE_NOT_ALLOWED")]
+    fn test_should_not_delegate_funds_if_cancelled() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                is_position_transfer_approval_required: Some(false),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 3)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).build());
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(MIN_FUNDING_AMOUNT - ATTACHED_DEPOSIT * 3)
+            .build());
+
+        contract.deposit();
+
+        assert_eq!(CampaignStatus::Funded, contract.get_status());
+
+        testing_env!(context
+            .predecessor_account_id(accounts(5))
+            .attached_deposit(0)
+            .build());
+
+        contract.cancel();
+        contract.delegate_funds("dao1".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_CREATOR")]
+    fn test_cancel_not_creator() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                is_position_transfer_approval_required: Some(false),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 3)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).build());
+
+        contract.cancel();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_CANCEL_NOT_ALLOWED")]
+    fn test_cancel_after_expiration() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                is_position_transfer_approval_required: Some(false),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 3)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).build());
+
+        testing_env!(context
+            .predecessor_account_id(accounts(5))
+            .block_timestamp(expires_at + 200)
+            .build());
+
+        contract.cancel();
+    }
+
+    #[test]
+    fn test_cancel_after_delegation_fails() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_failed_delegation(
+            &mut context,
+            expires_at,
+            PromiseResult::Failed,
+            PromiseResult::Successful("true".to_string().into_bytes()),
+        );
+
+        // The creator defaults to the signer of new
+        testing_env!(context
+            .predecessor_account_id(contract.get_creator_account_id())
+            .build());
+
+        contract.cancel();
+
+        assert_eq!(CampaignStatus::Cancelled, contract.get_status());
+
+        // Refunds open before the DELEGATION_RETRY_PERIOD, minus the FT creation deposit
+        testing_env!(context.predecessor_account_id(bob()).build());
+
+        contract.withdraw();
+
+        let event = EscrowEvent::Withdraw(WithdrawEvent {
+            account_id: bob(),
+            amount: U128((MIN_FUNDING_AMOUNT - FT_ATTACHED_DEPOSIT) / 2),
+            balance: U128(0),
+            total_funds: U128(MIN_FUNDING_AMOUNT / 2),
+            unpaid_funding_amount: U128(MIN_FUNDING_AMOUNT / 2),
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+    }
+
+    #[test]
+    fn test_refund_batch() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        for account_id in [bob(), carol(), accounts(5)] {
+            testing_env!(context
+                .predecessor_account_id(account_id)
+                .attached_deposit(ATTACHED_DEPOSIT)
+                .build());
+
+            contract.deposit();
+        }
+
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(0)
+            .block_timestamp(expires_at + 200)
+            .build());
+
+        assert_eq!(2, contract.refund_batch(None, 2));
+        assert_eq!(2, contract.get_refund_cursor());
+        assert_eq!(0, contract.deposits_of(&bob()));
+        assert_eq!(0, contract.deposits_of(&carol()));
+        assert_eq!(ATTACHED_DEPOSIT, contract.deposits_of(&accounts(5)));
+
+        assert_eq!(3, contract.refund_batch(None, 2));
+        assert_eq!(3, contract.get_refund_cursor());
+        assert_eq!(0, contract.deposits_of(&accounts(5)));
+        assert_eq!(0, contract.get_total_funds());
+        assert_eq!(0, contract.get_raised_funds());
+
+        let event = EscrowEvent::Withdraw(WithdrawEvent {
+            account_id: accounts(5),
+            amount: U128(ATTACHED_DEPOSIT),
+            balance: U128(0),
+            total_funds: U128(0),
+            unpaid_funding_amount: U128(MIN_FUNDING_AMOUNT),
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        // Nothing left to refund
+        testing_env!(context.build());
+
+        assert_eq!(3, contract.refund_batch(None, 2));
+        assert!(get_logs().is_empty());
+    }
+
+    #[test]
+    fn test_refund_batch_skips_withdrawn_depositors() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        for account_id in [bob(), carol()] {
+            testing_env!(context
+                .predecessor_account_id(account_id)
+                .attached_deposit(ATTACHED_DEPOSIT)
+                .build());
+
+            contract.deposit();
+        }
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(0)
+            .block_timestamp(expires_at + 200)
+            .build());
+
+        contract.withdraw();
+
+        testing_env!(context.predecessor_account_id(accounts(0)).build());
+
+        assert_eq!(2, contract.refund_batch(None, 10));
+        assert_eq!(
+            1,
+            get_logs()
+                .iter()
+                .filter(|log| log.starts_with("EVENT_JSON"))
+                .count()
+        );
+        assert_eq!(0, contract.deposits_of(&carol()));
+        assert_eq!(0, contract.get_total_funds());
+    }
+
+    #[test]
+    fn test_refund_batch_from_index_keeps_cursor() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        for account_id in [bob(), carol()] {
+            testing_env!(context
+                .predecessor_account_id(account_id)
+                .attached_deposit(ATTACHED_DEPOSIT)
+                .build());
+
+            contract.deposit();
+        }
+
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(0)
+            .block_timestamp(expires_at + 200)
+            .build());
+
+        assert_eq!(2, contract.refund_batch(Some(1), 10));
+        assert_eq!(0, contract.get_refund_cursor());
+        assert_eq!(ATTACHED_DEPOSIT, contract.deposits_of(&bob()));
+        assert_eq!(0, contract.deposits_of(&carol()));
+
+        assert_eq!(2, contract.refund_batch(None, 10));
+        assert_eq!(2, contract.get_refund_cursor());
+        assert_eq!(0, contract.deposits_of(&bob()));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_WITHDRAWAL_NOT_ALLOWED")]
+    fn test_refund_batch_not_allowed_while_funding() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+
+        contract.refund_batch(None, 10);
+    }
+
+    #[test]
+    fn test_deadline_extension_approved() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                extension_quorum_basis_points: Some(5_000),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 3)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(5))
+            .attached_deposit(0)
+            .build());
+
+        contract.propose_deadline_extension(expires_at + 1_000);
+
+        assert_eq!(Some(5_000), contract.get_extension_quorum_basis_points());
+        assert_eq!(
+            DEFAULT_EXTENSION_THRESHOLD_BASIS_POINTS,
+            contract.get_extension_threshold_basis_points()
+        );
+        assert_eq!(
+            expires_at + 1_000,
+            contract.get_deadline_extension().unwrap().expires_at
+        );
+
+        testing_env!(context.predecessor_account_id(bob()).build());
+
+        contract.vote_deadline_extension(true);
+
+        let event = EscrowEvent::DeadlineExtended(DeadlineExtendedEvent {
+            previous_expires_at: expires_at,
+            expires_at: expires_at + 1_000,
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        assert_eq!(expires_at + 1_000, contract.get_expiration_date());
+        assert!(contract.get_deadline_extension().is_none());
+
+        // Deposits are accepted until the new deadline
+        testing_env!(context
+            .predecessor_account_id(accounts(0))
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .block_timestamp(expires_at + 200)
+            .build());
+
+        contract.deposit();
+
+        assert_eq!(CampaignStatus::Funding, contract.get_status());
+        assert_eq!(ATTACHED_DEPOSIT, contract.deposits_of(&accounts(0)));
+    }
+
+    #[test]
+    fn test_deadline_extension_rejected() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                extension_quorum_basis_points: Some(5_000),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 3)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(5))
+            .attached_deposit(0)
+            .build());
+
+        contract.propose_deadline_extension(expires_at + 1_000);
+
+        // Below the quorum
+        testing_env!(context.predecessor_account_id(carol()).build());
+
+        contract.vote_deadline_extension(true);
+
+        assert_eq!(Some(true), contract.get_extension_vote_of(carol()));
+        assert_eq!(expires_at, contract.get_expiration_date());
+
+        // Quorum reached, threshold not exceeded
+        testing_env!(context.predecessor_account_id(bob()).build());
+
+        contract.vote_deadline_extension(false);
+
+        let extension = contract.get_deadline_extension().unwrap();
+
+        assert_eq!(ATTACHED_DEPOSIT, extension.votes_for.0);
+        assert_eq!(ATTACHED_DEPOSIT * 3, extension.votes_against.0);
+        assert_eq!(expires_at, contract.get_expiration_date());
+
+        testing_env!(context.block_timestamp(expires_at + 200).build());
+
+        assert_eq!(CampaignStatus::Expired, contract.get_status());
+    }
+
+    #[test]
+    fn test_deadline_extension_vote_follows_balance() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                extension_quorum_basis_points: Some(5_000),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 3)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(5))
+            .attached_deposit(0)
+            .build());
+
+        contract.propose_deadline_extension(expires_at + 1_000);
+
+        testing_env!(context.predecessor_account_id(carol()).build());
+
+        contract.vote_deadline_extension(true);
+
+        // A new vote replaces the previous one
+        contract.vote_deadline_extension(false);
+
+        let extension = contract.get_deadline_extension().unwrap();
+
+        assert_eq!(0, extension.votes_for.0);
+        assert_eq!(ATTACHED_DEPOSIT, extension.votes_against.0);
+
+        testing_env!(context.attached_deposit(ATTACHED_DEPOSIT).build());
+
+        contract.deposit();
+
+        assert_eq!(
+            ATTACHED_DEPOSIT * 2,
+            contract.get_deadline_extension().unwrap().votes_against.0
+        );
+
+        testing_env!(context.attached_deposit(0).build());
+
+        contract.transfer_position(accounts(0), U128(ATTACHED_DEPOSIT));
+
+        assert_eq!(
+            ATTACHED_DEPOSIT,
+            contract.get_deadline_extension().unwrap().votes_against.0
+        );
+        assert_eq!(None, contract.get_extension_vote_of(accounts(0)));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NO_DEADLINE_EXTENSION")]
+    fn test_deadline_extension_vote_after_expiration() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                extension_quorum_basis_points: Some(5_000),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 3)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(5))
+            .attached_deposit(0)
+            .build());
+
+        contract.propose_deadline_extension(expires_at + 1_000);
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .block_timestamp(expires_at + 200)
+            .build());
+
+        contract.vote_deadline_extension(true);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_CREATOR")]
+    fn test_propose_deadline_extension_not_creator() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                extension_quorum_basis_points: Some(5_000),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 3)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(accounts(5))
+            .attached_deposit(0)
+            .build());
+
+        contract.propose_deadline_extension(expires_at + 1_000);
+
+        testing_env!(context.predecessor_account_id(bob()).build());
+
+        contract.propose_deadline_extension(expires_at + 2_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DEADLINE_EXTENSION_NOT_ALLOWED")]
+    fn test_propose_deadline_extension_disabled() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        testing_env!(context
+            .predecessor_account_id(contract.get_creator_account_id())
+            .build());
+
+        contract.propose_deadline_extension(expires_at + 1_000);
+    }
+
+    #[test]
+    fn test_milestone_tranches_approved_by_depositors() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                tranche_basis_points: Some(vec![4_000, 3_000, 3_000]),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 10)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT * 5)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).build());
+
+        contract.delegate_funds("dao1".to_string());
+
+        testing_env!(
+            context.predecessor_account_id(alice()).build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful("true".to_string().into_bytes()),
+                PromiseResult::Successful("true".to_string().into_bytes())
+            ],
+        );
+
+        assert!(contract.on_delegate_callback("dao1".to_string()));
+
+        // 10 NEAR for the DAO, 4 NEAR released on delegation
+        assert_eq!(1, contract.get_released_tranches());
+        assert_eq!(ATTACHED_DEPOSIT * 6, contract.get_undisbursed_funds());
+        assert_eq!(ATTACHED_DEPOSIT * 6, contract.get_total_funds());
+        assert!(contract.is_milestone_pending());
+
+        testing_env!(context.predecessor_account_id(bob()).build());
+
+        contract.vote_milestone(true);
+
+        let event = EscrowEvent::MilestoneApproved(MilestoneApprovedEvent {
+            milestone: 1,
+            amount: U128(ATTACHED_DEPOSIT * 3),
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        assert_eq!(2, contract.get_released_tranches());
+        assert_eq!(ATTACHED_DEPOSIT * 3, contract.get_undisbursed_funds());
+        assert_eq!(ATTACHED_DEPOSIT * 3, contract.get_total_funds());
+        assert_eq!(0, contract.get_pending_dao_funds());
+        assert_eq!(None, contract.get_milestone_vote_of(bob()));
+
+        // Below the majority of the allocated funds
+        testing_env!(context.predecessor_account_id(carol()).build());
+
+        contract.vote_milestone(true);
+
+        assert_eq!(Some(true), contract.get_milestone_vote_of(carol()));
+        assert_eq!(2, contract.get_released_tranches());
+
+        testing_env!(context.predecessor_account_id(bob()).build());
+
+        contract.vote_milestone(true);
+
+        assert_eq!(3, contract.get_released_tranches());
+        assert_eq!(0, contract.get_undisbursed_funds());
+        assert_eq!(0, contract.get_total_funds());
+        assert!(!contract.is_milestone_pending());
+    }
+
+    #[test]
+    fn test_milestone_rejected_by_verifier() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                tranche_basis_points: Some(vec![4_000, 3_000, 3_000]),
+                milestone_verifier_account_id: Some(accounts(5)),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 10)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT * 5)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).build());
+
+        contract.delegate_funds("dao1".to_string());
+
+        testing_env!(
+            context.predecessor_account_id(alice()).build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful("true".to_string().into_bytes()),
+                PromiseResult::Successful("true".to_string().into_bytes())
+            ],
+        );
+
+        assert!(contract.on_delegate_callback("dao1".to_string()));
+
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+
+        contract.vote_milestone(false);
+
+        let event = EscrowEvent::MilestoneRejected(MilestoneRejectedEvent {
+            milestone: 1,
+            undisbursed_funds: U128(ATTACHED_DEPOSIT * 6),
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        assert_eq!(CampaignStatus::MilestoneRejected, contract.get_status());
+        assert!(contract.is_withdrawal_allowed());
+
+        testing_env!(context.predecessor_account_id(bob()).build());
+
+        contract.withdraw();
+
+        assert_eq!(ATTACHED_DEPOSIT * 4, contract.get_tranche_refund_of(&bob()));
+        assert_eq!(ATTACHED_DEPOSIT * 10, contract.deposits_of(&bob()));
+        assert_eq!(ATTACHED_DEPOSIT * 2, contract.get_total_funds());
+
+        // Refunded depositors are skipped
+        assert_eq!(2, contract.refund_batch(None, 10));
+        assert_eq!(
+            ATTACHED_DEPOSIT * 2,
+            contract.get_tranche_refund_of(&carol())
+        );
+        assert_eq!(0, contract.get_total_funds());
+    }
+
+    #[test]
+    fn test_tranche_refund_restored_on_failure() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                tranche_basis_points: Some(vec![4_000, 3_000, 3_000]),
+                milestone_verifier_account_id: Some(accounts(5)),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 10)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT * 5)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).build());
+
+        contract.delegate_funds("dao1".to_string());
+
+        testing_env!(
+            context.predecessor_account_id(alice()).build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful("true".to_string().into_bytes()),
+                PromiseResult::Successful("true".to_string().into_bytes())
+            ],
+        );
+
+        assert!(contract.on_delegate_callback("dao1".to_string()));
+
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+
+        contract.vote_milestone(false);
+
+        testing_env!(context.predecessor_account_id(bob()).build());
+
+        contract.withdraw();
+
+        testing_env!(
+            context.predecessor_account_id(alice()).build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+
+        assert!(!contract.on_tranche_refund_callback(bob(), U128(ATTACHED_DEPOSIT * 4)));
+
+        assert_eq!(0, contract.get_tranche_refund_of(&bob()));
+        assert_eq!(ATTACHED_DEPOSIT * 6, contract.get_total_funds());
+
+        testing_env!(context.predecessor_account_id(bob()).build());
+
+        contract.withdraw();
+
+        assert_eq!(ATTACHED_DEPOSIT * 4, contract.get_tranche_refund_of(&bob()));
+    }
+
+    #[test]
+    fn test_tranche_refund_dust() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                tranche_basis_points: Some(vec![4_000, 3_000, 3_000]),
+                milestone_verifier_account_id: Some(accounts(5)),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 10 + 1)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT * 5 - 1)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).build());
+
+        contract.delegate_funds("dao1".to_string());
+
+        testing_env!(
+            context.predecessor_account_id(alice()).build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful("true".to_string().into_bytes()),
+                PromiseResult::Successful("true".to_string().into_bytes())
+            ],
+        );
+
+        assert!(contract.on_delegate_callback("dao1".to_string()));
+
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+
+        contract.vote_milestone(false);
+
+        testing_env!(context.predecessor_account_id(bob()).build());
+
+        contract.withdraw();
+
+        assert_eq!(ATTACHED_DEPOSIT * 4, contract.get_tranche_refund_of(&bob()));
+
+        testing_env!(context.predecessor_account_id(carol()).build());
+
+        contract.withdraw();
+
+        // The last refund takes the rounding dust
+        assert_eq!(
+            ATTACHED_DEPOSIT * 2,
+            contract.get_tranche_refund_of(&carol())
+        );
+        assert_eq!(0, contract.get_total_funds());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_MILESTONE_VERIFIER")]
+    fn test_vote_milestone_not_verifier() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                tranche_basis_points: Some(vec![4_000, 3_000, 3_000]),
+                milestone_verifier_account_id: Some(accounts(5)),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 10)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT * 5)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).build());
+
+        contract.delegate_funds("dao1".to_string());
+
+        testing_env!(
+            context.predecessor_account_id(alice()).build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful("true".to_string().into_bytes()),
+                PromiseResult::Successful("true".to_string().into_bytes())
+            ],
+        );
+
+        assert!(contract.on_delegate_callback("dao1".to_string()));
+
+        testing_env!(context.predecessor_account_id(bob()).build());
+
+        contract.vote_milestone(true);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NO_PENDING_MILESTONE")]
+    fn test_vote_milestone_without_tranches() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+
+        contract.vote_milestone(true);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_TRANCHES")]
+    fn test_new_invalid_tranches() {
+        setup_context();
+
+        setup_contract_with(
+            add_expires_at_nanos(100),
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                tranche_basis_points: Some(vec![5_000, 4_000]),
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    fn test_release_conditions_met() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                release_conditions: Some(vec![
+                    ReleaseCondition::Attestation {
+                        account_id: accounts(5),
+                    },
+                    ReleaseCondition::Oracle {
+                        contract_id: accounts(0),
+                        method_name: "is_appraised".to_string(),
+                        args: "{}".to_string(),
+                    },
+                ]),
+                release_conditions_deadline: Some(expires_at + 1_000),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).build());
+
+        assert_eq!(CampaignStatus::Funded, contract.get_status());
+        assert!(!contract.is_delegation_allowed());
+
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+
+        contract.attest();
+
+        let event = EscrowEvent::ReleaseConditionMet(ReleaseConditionMetEvent {
+            index: 0,
+            condition: ReleaseCondition::Attestation {
+                account_id: accounts(5),
+            },
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+        assert_eq!(vec![true, false], contract.get_met_release_conditions());
+
+        testing_env!(context.predecessor_account_id(bob()).build());
+
+        contract.check_release_condition(1);
+
+        testing_env!(
+            context.predecessor_account_id(alice()).build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful("true".to_string().into_bytes())],
+        );
+
+        assert!(contract.on_release_condition_callback(1));
+        assert!(contract.are_release_conditions_met());
+        assert!(contract.is_delegation_allowed());
+
+        contract.delegate_funds("dao1".to_string());
+
+        assert_eq!(CampaignStatus::Delegating, contract.get_status());
+    }
+
+    #[test]
+    fn test_release_condition_oracle_returns_false() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                release_conditions: Some(vec![
+                    ReleaseCondition::Attestation {
+                        account_id: accounts(5),
+                    },
+                    ReleaseCondition::Oracle {
+                        contract_id: accounts(0),
+                        method_name: "is_appraised".to_string(),
+                        args: "{}".to_string(),
+                    },
+                ]),
+                release_conditions_deadline: Some(expires_at + 1_000),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).build());
+
+        testing_env!(
+            context.predecessor_account_id(alice()).build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful("false".to_string().into_bytes())],
+        );
+
+        assert!(!contract.on_release_condition_callback(1));
+        assert_eq!(vec![false, false], contract.get_met_release_conditions());
+    }
+
+    #[test]
+    fn test_release_conditions_not_met_by_deadline() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                release_conditions: Some(vec![
+                    ReleaseCondition::Attestation {
+                        account_id: accounts(5),
+                    },
+                    ReleaseCondition::Oracle {
+                        contract_id: accounts(0),
+                        method_name: "is_appraised".to_string(),
+                        args: "{}".to_string(),
+                    },
+                ]),
+                release_conditions_deadline: Some(expires_at + 1_000),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).build());
+
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+
+        contract.attest();
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .block_timestamp(expires_at + 1_200)
+            .build());
+
+        assert_eq!(CampaignStatus::Expired, contract.get_status());
+        assert!(contract.is_withdrawal_allowed());
+        assert!(!contract.is_release_condition_check_allowed());
+
+        contract.withdraw();
+
+        assert_eq!(0, contract.deposits_of(&bob()));
+        assert_eq!(0, contract.get_total_funds());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_RELEASE_CONDITIONS_NOT_MET")]
+    fn test_delegate_funds_release_conditions_not_met() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                release_conditions: Some(vec![
+                    ReleaseCondition::Attestation {
+                        account_id: accounts(5),
+                    },
+                    ReleaseCondition::Oracle {
+                        contract_id: accounts(0),
+                        method_name: "is_appraised".to_string(),
+                        args: "{}".to_string(),
+                    },
+                ]),
+                release_conditions_deadline: Some(expires_at + 1_000),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).build());
+
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+
+        contract.attest();
+
+        contract.delegate_funds("dao1".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_ATTESTER")]
+    fn test_attest_not_attester() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                release_conditions: Some(vec![
+                    ReleaseCondition::Attestation {
+                        account_id: accounts(5),
+                    },
+                    ReleaseCondition::Oracle {
+                        contract_id: accounts(0),
+                        method_name: "is_appraised".to_string(),
+                        args: "{}".to_string(),
+                    },
+                ]),
+                release_conditions_deadline: Some(expires_at + 1_000),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).build());
+
+        testing_env!(context.predecessor_account_id(carol()).build());
+
+        contract.attest();
+    }
+
+    #[test]
+    fn test_delegation_deadline_opens_withdrawals() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                delegation_window: Some(1_000),
+                keeper_reward_basis_points: Some(50),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(1_000, contract.get_delegation_window());
+        assert_eq!(expires_at + 1_000, contract.get_delegation_deadline());
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .attached_deposit(0)
+            .block_timestamp(expires_at + 200)
+            .build());
+
+        assert!(contract.is_delegation_allowed());
+        assert!(!contract.is_withdrawal_allowed());
+
+        testing_env!(context.block_timestamp(expires_at + 1_200).build());
+
+        assert_eq!(CampaignStatus::Expired, contract.get_status());
+        assert!(!contract.is_delegation_allowed());
+        assert!(contract.is_withdrawal_allowed());
+
+        contract.withdraw();
+
+        assert_eq!(0, contract.deposits_of(&bob()));
+        assert_eq!(0, contract.get_total_funds());
+    }
+
+    #[test]
+    fn test_delegate_funds_keeper_reward() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                delegation_window: Some(1_000),
+                keeper_reward_basis_points: Some(50),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(0)
+            .build());
+
+        contract.delegate_funds("dao1".to_string());
+
+        let keeper_reward = MIN_FUNDING_AMOUNT * 50 / 10_000;
+
+        assert_eq!(keeper_reward, contract.get_keeper_reward());
+        assert_eq!(Some(carol()), contract.get_keeper_account_id());
+
+        let event = EscrowEvent::DelegateStarted(DelegateStartedEvent {
+            dao_name: "dao1".to_string(),
+            dao_amount: U128(MIN_FUNDING_AMOUNT - FT_ATTACHED_DEPOSIT - keeper_reward),
+            ft_amount: U128(FT_ATTACHED_DEPOSIT),
+            fee_amount: U128(0),
+            depositors_count: 1,
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        testing_env!(
+            context.predecessor_account_id(alice()).build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful("true".to_string().into_bytes()),
+                PromiseResult::Successful("true".to_string().into_bytes())
+            ],
+        );
+
+        assert!(contract.on_delegate_callback("dao1".to_string()));
+
+        assert!(get_logs().contains(&format!(
+            "{} rewarded with {} tokens for the delegation",
+            carol(),
+            keeper_reward
+        )));
+        assert_eq!(0, contract.get_total_funds());
+    }
+
+    #[test]
+    fn test_keeper_reward_refunded_on_failed_delegation() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                delegation_window: Some(1_000),
+                keeper_reward_basis_points: Some(50),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).build());
+
+        contract.delegate_funds("dao1".to_string());
+
+        testing_env!(
+            context.predecessor_account_id(alice()).build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Failed,
+                PromiseResult::Successful("true".to_string().into_bytes())
+            ],
+        );
+
+        assert!(!contract.on_delegate_callback("dao1".to_string()));
+        assert_eq!(0, contract.get_keeper_reward());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_KEEPER_REWARD_BASIS_POINTS")]
+    fn test_new_invalid_keeper_reward_basis_points() {
+        setup_context();
+
+        setup_contract_with(
+            add_expires_at_nanos(100),
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                keeper_reward_basis_points: Some(MAX_KEEPER_REWARD_BASIS_POINTS + 1),
+                ..Default::default()
+            },
+        );
+    }
+
+    #[test]
+    fn test_withdraw_early_during_cooling_off() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                cooling_off_period: Some(1_000),
+                early_exit_penalty_basis_points: Some(1_000),
+                is_early_exit_penalty_to_creator: Some(false),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 3)
+            .block_timestamp(1_000)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).block_timestamp(2_000).build());
+
+        assert_eq!(Some(0), contract.get_early_exit_penalty_of(&bob()));
+
+        contract.withdraw_early();
+
+        let event = EscrowEvent::Withdraw(WithdrawEvent {
+            account_id: bob(),
+            amount: U128(ATTACHED_DEPOSIT * 3),
+            balance: U128(0),
+            total_funds: U128(0),
+            unpaid_funding_amount: U128(MIN_FUNDING_AMOUNT),
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        assert_eq!(0, contract.deposits_of(&bob()));
+        assert_eq!(0, contract.get_raised_funds());
+        assert_eq!(MIN_FUNDING_AMOUNT, contract.get_unpaid_funding_amount());
+        assert_eq!(0, contract.get_early_exit_penalties());
+    }
+
+    #[test]
+    fn test_withdraw_early_cooling_off_amount_once_funded() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(10_000);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                cooling_off_period: Some(1_000),
+                early_exit_penalty_basis_points: Some(1_000),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 10)
+            .block_timestamp(expires_at - 5_000)
+            .build());
+
+        contract.deposit();
+
+        // The cap is reached during the cooling-off period of the second deposit
+        testing_env!(context
+            .attached_deposit(MIN_FUNDING_AMOUNT - ATTACHED_DEPOSIT * 10)
+            .block_timestamp(expires_at - 2_000)
+            .build());
+
+        contract.deposit();
+
+        assert_eq!(CampaignStatus::Funded, contract.get_status());
+
+        testing_env!(context
+            .attached_deposit(0)
+            .block_timestamp(expires_at - 1_500)
+            .build());
+
+        assert_eq!(Some(0), contract.get_early_exit_penalty_of(&bob()));
+        assert_eq!(None, contract.get_early_exit_penalty_of(&carol()));
+
+        contract.withdraw_early();
+
+        let event = EscrowEvent::Withdraw(WithdrawEvent {
+            account_id: bob(),
+            amount: U128(MIN_FUNDING_AMOUNT - ATTACHED_DEPOSIT * 10),
+            balance: U128(ATTACHED_DEPOSIT * 10),
+            total_funds: U128(ATTACHED_DEPOSIT * 10),
+            unpaid_funding_amount: U128(MIN_FUNDING_AMOUNT - ATTACHED_DEPOSIT * 10),
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        // Funding reopens, the older deposit is still charged the penalty
+        assert_eq!(CampaignStatus::Funding, contract.get_status());
+        assert_eq!(ATTACHED_DEPOSIT * 10, contract.deposits_of(&bob()));
+        assert_eq!(0, contract.get_cooling_off_amount_of(&bob()));
+        assert_eq!(0, contract.get_early_exit_penalties());
+        assert_eq!(
+            Some(ATTACHED_DEPOSIT),
+            contract.get_early_exit_penalty_of(&bob())
+        );
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(MIN_FUNDING_AMOUNT - ATTACHED_DEPOSIT * 10)
+            .build());
+
+        contract.deposit();
+
+        assert_eq!(CampaignStatus::Funded, contract.get_status());
+
+        // Without a deposit in its cooling-off period, bob may not withdraw once funded
+        assert_eq!(None, contract.get_early_exit_penalty_of(&bob()));
+    }
+
+    #[test]
+    fn test_withdraw_early_penalty_stays_in_pot() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                cooling_off_period: Some(1_000),
+                early_exit_penalty_basis_points: Some(1_000),
+                is_early_exit_penalty_to_creator: Some(false),
+                ..Default::default()
+            },
+        );
+
+        // Only the second deposit is still in its cooling-off period
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 2)
+            .block_timestamp(1_000)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .block_timestamp(3_000)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).block_timestamp(3_500).build());
+
+        let penalty = ATTACHED_DEPOSIT * 2 / 10;
+
+        assert_eq!(Some(penalty), contract.get_early_exit_penalty_of(&bob()));
+
+        contract.withdraw_early();
+
+        assert_eq!(penalty, contract.get_early_exit_penalties());
+        assert_eq!(penalty, contract.get_total_funds());
+        assert_eq!(0, contract.get_raised_funds());
+        assert_eq!(MIN_FUNDING_AMOUNT, contract.get_unpaid_funding_amount());
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(MIN_FUNDING_AMOUNT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).build());
+
+        contract.delegate_funds("dao1".to_string());
+
+        let event = EscrowEvent::DelegateStarted(DelegateStartedEvent {
+            dao_name: "dao1".to_string(),
+            dao_amount: U128(MIN_FUNDING_AMOUNT - FT_ATTACHED_DEPOSIT + penalty),
+            ft_amount: U128(FT_ATTACHED_DEPOSIT),
+            fee_amount: U128(0),
+            depositors_count: 1,
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        testing_env!(
+            context.predecessor_account_id(alice()).build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![
+                PromiseResult::Successful("true".to_string().into_bytes()),
+                PromiseResult::Successful("true".to_string().into_bytes())
+            ],
+        );
+
+        assert!(contract.on_delegate_callback("dao1".to_string()));
+        assert_eq!(0, contract.get_total_funds());
+    }
+
+    #[test]
+    fn test_withdraw_early_penalty_to_creator() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                cooling_off_period: Some(1_000),
+                early_exit_penalty_basis_points: Some(1_000),
+                is_early_exit_penalty_to_creator: Some(true),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .block_timestamp(1_000)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).block_timestamp(5_000).build());
+
+        contract.withdraw_early();
+
+        assert_eq!(0, contract.get_early_exit_penalties());
+        assert_eq!(0, contract.get_total_funds());
+        assert_eq!(0, contract.get_raised_funds());
+        assert!(get_function_call_gas(Some("on_payout_callback")) > 0);
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+
+        // The penalty stays claimable by the creator if its transfer fails
+        assert!(!contract.on_payout_callback(accounts(5), U128(ATTACHED_DEPOSIT / 10)));
+        assert_eq!(
+            ATTACHED_DEPOSIT / 10,
+            contract.get_pending_payout_of(&accounts(5))
+        );
+    }
+
+    #[test]
+    fn test_withdraw_early_exit_penalties_after_expiration() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                cooling_off_period: Some(1_000),
+                early_exit_penalty_basis_points: Some(1_000),
+                is_early_exit_penalty_to_creator: Some(false),
+                ..Default::default()
+            },
+        );
+
+        for account_id in [bob(), carol()] {
+            testing_env!(context
+                .predecessor_account_id(account_id)
+                .attached_deposit(ATTACHED_DEPOSIT)
+                .block_timestamp(1_000)
+                .build());
+
+            contract.deposit();
+        }
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(0)
+            .block_timestamp(5_000)
+            .build());
+
+        contract.withdraw_early();
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .block_timestamp(expires_at + 200)
+            .build());
+
+        contract.withdraw();
+
+        assert_eq!(ATTACHED_DEPOSIT / 10, contract.get_total_funds());
+
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+
+        contract.withdraw_early_exit_penalties();
+
+        assert_eq!(0, contract.get_early_exit_penalties());
+        assert_eq!(0, contract.get_total_funds());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_EARLY_WITHDRAWAL_NOT_ALLOWED")]
+    fn test_withdraw_early_not_allowed() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+
+        contract.withdraw_early();
+    }
+
+    #[test]
+    fn test_allowlist() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                is_allowlist_enabled: Some(true),
+                ..Default::default()
+            },
+        );
+
+        assert!(contract.is_allowlist_enabled());
+        assert!(!contract.is_allowlisted(bob()));
+
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+
+        contract.add_to_allowlist(vec![bob(), carol()]);
+
+        assert!(contract.is_allowlisted(bob()));
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+
+        assert_eq!(ATTACHED_DEPOSIT, contract.deposits_of(&bob()));
+
+        testing_env!(context
+            .predecessor_account_id(accounts(5))
+            .attached_deposit(0)
+            .build());
+
+        contract.remove_from_allowlist(vec![carol()]);
+
+        assert!(!contract.is_allowlisted(carol()));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_ALLOWLISTED")]
+    fn test_allowlist_deposit_not_allowlisted() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                is_allowlist_enabled: Some(true),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_ALLOWLISTED")]
+    fn test_allowlist_transfer_position_not_allowlisted() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                is_allowlist_enabled: Some(true),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+
+        contract.add_to_allowlist(vec![bob()]);
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+
+        testing_env!(context.attached_deposit(0).build());
+
+        contract.transfer_position(carol(), U128(ATTACHED_DEPOSIT));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_CREATOR")]
+    fn test_add_to_allowlist_not_creator() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                is_allowlist_enabled: Some(true),
+                ..Default::default()
+            },
         );
+
+        testing_env!(context.predecessor_account_id(bob()).build());
+
+        contract.add_to_allowlist(vec![bob()]);
     }
 
     #[test]
-    fn test_get_shares_of() {
+    fn test_attestation_registry_verified_deposit() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                is_allowlist_enabled: Some(false),
+                attestation_registry_id: Some(accounts(0)),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(Some(accounts(0)), contract.get_attestation_registry_id());
+        assert!(!contract.is_position_transfer_allowed());
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+
+        // Recorded once the registry verifies the depositor
+        assert_eq!(0, contract.deposits_of(&bob()));
+
+        testing_env!(
+            context
+                .predecessor_account_id(alice())
+                .attached_deposit(0)
+                .build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful("true".to_string().into_bytes())],
+        );
+
+        assert_eq!(
+            U128(0),
+            contract.on_verify_deposit_callback(bob(), U128(ATTACHED_DEPOSIT), bob())
+        );
+
+        assert_eq!(ATTACHED_DEPOSIT, contract.deposits_of(&bob()));
+        assert_eq!(ATTACHED_DEPOSIT, contract.get_total_funds());
+    }
+
+    #[test]
+    fn test_attestation_registry_rejected_deposit() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                is_allowlist_enabled: Some(false),
+                attestation_registry_id: Some(accounts(0)),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(
+            context.predecessor_account_id(alice()).build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful("false".to_string().into_bytes())],
+        );
+
+        assert_eq!(
+            U128(ATTACHED_DEPOSIT),
+            contract.on_verify_deposit_callback(bob(), U128(ATTACHED_DEPOSIT), bob())
+        );
+
+        assert!(get_logs().contains(&format!("ERR_NOT_VERIFIED: {} deposit refunded", bob())));
+        assert_eq!(0, contract.deposits_of(&bob()));
+        assert_eq!(0, contract.get_total_funds());
+    }
+
+    #[test]
+    fn test_attestation_registry_deposit_no_longer_allowed() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                is_allowlist_enabled: Some(false),
+                attestation_registry_id: Some(accounts(0)),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(
+            context
+                .predecessor_account_id(alice())
+                .block_timestamp(expires_at + 200)
+                .build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful("true".to_string().into_bytes())],
+        );
+
+        assert_eq!(
+            U128(ATTACHED_DEPOSIT),
+            contract.on_verify_deposit_callback(bob(), U128(ATTACHED_DEPOSIT), bob())
+        );
+
+        assert_eq!(0, contract.deposits_of(&bob()));
+    }
+
+    #[test]
+    fn test_storage_fee_charged_on_first_deposit() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                is_storage_fee_charged: Some(true),
+                ..Default::default()
+            },
+        );
+
+        assert!(contract.is_storage_fee_charged());
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+
+        let storage_fee = contract.get_storage_fee_of(&bob());
+
+        assert!(storage_fee > 0);
+        assert_eq!(storage_fee, contract.get_total_storage_fees());
+        assert_eq!(ATTACHED_DEPOSIT - storage_fee, contract.deposits_of(&bob()));
+
+        contract.deposit();
+
+        assert_eq!(storage_fee, contract.get_total_storage_fees());
+        assert_eq!(
+            ATTACHED_DEPOSIT * 2 - storage_fee,
+            contract.deposits_of(&bob())
+        );
+    }
+
+    #[test]
+    fn test_storage_fee_refunded_on_withdraw() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                is_storage_fee_charged: Some(true),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+
+        let storage_fee = contract.get_storage_fee_of(&bob());
+
+        testing_env!(context
+            .attached_deposit(0)
+            .block_timestamp(expires_at + 100)
+            .build());
+
+        contract.withdraw();
+
+        let event = EscrowEvent::Withdraw(WithdrawEvent {
+            account_id: bob(),
+            amount: U128(ATTACHED_DEPOSIT - storage_fee),
+            balance: U128(0),
+            total_funds: U128(0),
+            unpaid_funding_amount: U128(MIN_FUNDING_AMOUNT),
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        assert_eq!(0, contract.get_storage_fee_of(&bob()));
+        assert_eq!(0, contract.get_total_storage_fees());
+    }
+
+    #[test]
+    fn test_storage_fee_matches_storage_usage() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                is_storage_fee_charged: Some(true),
+                cooling_off_period: Some(1_000),
+                early_bonus_basis_points: Some(1_000),
+                extension_quorum_basis_points: Some(5_000),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        let initial_storage_usage = env::storage_usage();
+
+        contract.deposit();
+
+        // Every entry written by the first deposit is charged
+        let storage_fee = contract.get_storage_fee_of(&bob());
+
+        assert!(contract.get_bonus_weight_of(&bob()) > 0);
+        assert!(contract.get_cooling_off_amount_of(&bob()) > 0);
+        assert_eq!(
+            Balance::from(env::storage_usage() - initial_storage_usage) * env::storage_byte_cost(),
+            storage_fee
+        );
+
+        testing_env!(context
+            .attached_deposit(0)
+            .block_timestamp(expires_at + 100)
+            .build());
+
+        let initial_storage_usage = env::storage_usage();
+
+        contract.withdraw();
+
+        // Only the released storage is refunded, the rest covers the entries that are kept
+        let refunded_storage_fee =
+            Balance::from(initial_storage_usage - env::storage_usage()) * env::storage_byte_cost();
+        let transfers: Vec<Balance> = near_sdk::test_utils::get_created_receipts()
+            .iter()
+            .flat_map(|receipt| receipt.actions.iter())
+            .filter_map(|action| match action {
+                near_sdk::mock::VmAction::Transfer { deposit } => Some(*deposit),
+                _ => None,
+            })
+            .collect();
+
+        assert!(refunded_storage_fee > 0 && refunded_storage_fee < storage_fee);
+        assert_eq!(
+            vec![ATTACHED_DEPOSIT - storage_fee + refunded_storage_fee],
+            transfers
+        );
+        assert_eq!(0, contract.get_total_storage_fees());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INSUFFICIENT_STORAGE_FEE")]
+    fn test_storage_fee_not_covered_by_deposit() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                is_storage_fee_charged: Some(true),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(1)
+            .build());
+
+        contract.deposit();
+    }
+
+    #[test]
+    fn test_delegate_funds_keeps_storage_reserve() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                is_storage_fee_charged: Some(true),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT * 2)
+            .build());
+
+        contract.deposit();
+
+        // The pooled funds paid for part of the storage, the reserve is withheld from the DAO
+        testing_env!(context
+            .attached_deposit(0)
+            .account_balance(contract.get_total_funds())
+            .block_timestamp(expires_at + 200)
+            .build());
+
+        let storage_reserve = contract.get_storage_reserve();
+
+        contract.delegate_funds("dao1".to_string());
+
+        let event = EscrowEvent::DelegateStarted(DelegateStartedEvent {
+            dao_name: "dao1".to_string(),
+            dao_amount: U128(MIN_FUNDING_AMOUNT - FT_ATTACHED_DEPOSIT - storage_reserve),
+            ft_amount: U128(FT_ATTACHED_DEPOSIT),
+            fee_amount: U128(0),
+            depositors_count: 1,
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+    }
+
+    #[test]
+    fn test_storage_fee_refunded_after_full_position_transfer() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                is_position_transfer_approval_required: Some(false),
+                is_storage_fee_charged: Some(true),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 3)
+            .build());
+
+        contract.deposit();
+
+        let balance = contract.deposits_of(&bob());
+
+        testing_env!(context.attached_deposit(0).build());
+
+        contract.transfer_position(carol(), U128(balance));
+
+        // The sender gets its storage fee back, the receiver is charged its own
+        let storage_fee = contract.get_storage_fee_of(&carol());
+
+        assert!(storage_fee > 0);
+        assert!(get_function_call_gas(Some("on_payout_callback")) > 0);
+        assert_eq!(0, contract.get_storage_fee_of(&bob()));
+        assert_eq!(storage_fee, contract.get_total_storage_fees());
+        assert_eq!(1, contract.get_depositors_count());
+        assert_eq!(balance - storage_fee, contract.deposits_of(&carol()));
+        assert_eq!(balance - storage_fee, contract.get_raised_funds());
+        assert_eq!(balance - storage_fee, contract.get_total_funds());
+
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .block_timestamp(expires_at + 100)
+            .build());
+
+        contract.withdraw();
+
+        let event = EscrowEvent::Withdraw(WithdrawEvent {
+            account_id: carol(),
+            amount: U128(balance - storage_fee),
+            balance: U128(0),
+            total_funds: U128(0),
+            unpaid_funding_amount: U128(MIN_FUNDING_AMOUNT),
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        assert_eq!(0, contract.get_storage_fee_of(&carol()));
+        assert_eq!(0, contract.get_total_storage_fees());
+    }
+
+    #[test]
+    fn test_delegate_funds_storage_reserve_excludes_fee_and_keeper_reward() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                fee_basis_points: Some(FEE_BASIS_POINTS),
+                fee_account_id: Some("fees.near".parse().unwrap()),
+                keeper_reward_basis_points: Some(50),
+                is_storage_fee_charged: Some(true),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT * 2)
+            .build());
+
+        contract.deposit();
+
+        // The fee and the keeper reward paid by on_delegate_callback do not cover the reserve
+        testing_env!(context
+            .attached_deposit(0)
+            .account_balance(contract.get_total_funds())
+            .block_timestamp(expires_at + 200)
+            .build());
+
+        let storage_reserve = contract.get_storage_reserve();
+
+        contract.delegate_funds("dao1".to_string());
+
+        let fee_amount = contract.get_fee_amount();
+        let keeper_reward = contract.get_keeper_reward();
+
+        assert!(fee_amount > 0);
+        assert!(keeper_reward > 0);
+
+        let event = EscrowEvent::DelegateStarted(DelegateStartedEvent {
+            dao_name: "dao1".to_string(),
+            dao_amount: U128(
+                MIN_FUNDING_AMOUNT
+                    - FT_ATTACHED_DEPOSIT
+                    - fee_amount
+                    - keeper_reward
+                    - storage_reserve,
+            ),
+            ft_amount: U128(FT_ATTACHED_DEPOSIT),
+            fee_amount: U128(fee_amount),
+            depositors_count: 1,
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_STORAGE_RESERVE_NOT_COVERED")]
+    fn test_delegate_funds_storage_reserve_not_covered() {
         let mut context = setup_context();
 
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                is_storage_fee_charged: Some(true),
+                ..Default::default()
+            },
+        );
+
         testing_env!(context
-            .signer_account_id(bob())
-            .attached_deposit(ATTACHED_DEPOSIT)
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT * 2)
             .build());
 
-        let expires_at = add_expires_at_nanos(100);
-
-        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
-
         contract.deposit();
 
-        assert_eq!(
-            0,
-            contract.get_shares_of(&alice()),
-            "Account deposits should be 0"
-        );
+        testing_env!(context
+            .attached_deposit(0)
+            .account_balance(contract.get_total_funds())
+            .storage_usage(10_000_000)
+            .block_timestamp(expires_at + 200)
+            .build());
 
-        assert_eq!(
-            ATTACHED_DEPOSIT * 1000 / contract.funding_amount_limit,
-            contract.get_shares_of(&bob()),
-            "Proportion deposit of Bob should be 8"
-        );
+        contract.delegate_funds("dao1".to_string());
     }
 
+    const EARLY_BONUS_PERIOD: u64 = 100;
+
     #[test]
-    fn test_get_deposits() {
+    fn test_early_bonus_weights_shares() {
         let mut context = setup_context();
 
-        testing_env!(context
-            .signer_account_id(bob())
-            .attached_deposit(ATTACHED_DEPOSIT)
-            .build());
+        let expires_at = add_expires_at_nanos(1_000);
 
-        let expires_at = add_expires_at_nanos(100);
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                early_bonus_basis_points: Some(1_000),
+                early_bonus_period: Some(EARLY_BONUS_PERIOD),
+                ..Default::default()
+            },
+        );
 
-        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+        let started_at = contract.get_funding_started_at();
 
-        contract.deposit();
+        assert_eq!(1_000, contract.get_current_bonus_basis_points());
 
-        assert_eq!(
-            contract.get_deposits(),
-            vec![(bob(), ATTACHED_DEPOSIT)],
-            "Gets all deposits as vec"
-        );
-    }
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(MIN_FUNDING_AMOUNT / 3)
+            .build());
 
-    #[test]
-    fn test_get_deposit_accounts() {
-        let mut context = setup_context();
+        contract.deposit();
 
         testing_env!(context
-            .signer_account_id(bob())
-            .attached_deposit(ATTACHED_DEPOSIT)
+            .predecessor_account_id(carol())
+            .block_timestamp(started_at + EARLY_BONUS_PERIOD / 2)
             .build());
 
-        let expires_at = add_expires_at_nanos(100);
-
-        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+        assert_eq!(500, contract.get_current_bonus_basis_points());
 
         contract.deposit();
 
         testing_env!(context
-            .signer_account_id(carol())
-            .attached_deposit(ATTACHED_DEPOSIT)
+            .predecessor_account_id(accounts(5))
+            .block_timestamp(started_at + EARLY_BONUS_PERIOD)
             .build());
 
+        assert_eq!(0, contract.get_current_bonus_basis_points());
+
         contract.deposit();
 
         assert_eq!(
-            vec!["bob.near", "carol.near"],
-            contract.get_deposit_accounts(),
+            MIN_FUNDING_AMOUNT / 30,
+            contract.get_bonus_weight_of(&bob())
         );
-    }
-
-    #[test]
-    fn test_get_dao_factory_account_id() {
-        let expires_at = add_expires_at_nanos(100);
-
-        let contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
-
         assert_eq!(
-            accounts(3),
-            contract.get_dao_factory_account_id(),
-            "Recipient account id should be 'danny.near'"
+            MIN_FUNDING_AMOUNT / 60,
+            contract.get_bonus_weight_of(&carol())
+        );
+        assert_eq!(0, contract.get_bonus_weight_of(&accounts(5)));
+        assert_eq!(
+            MIN_FUNDING_AMOUNT / 30 + MIN_FUNDING_AMOUNT / 60,
+            contract.get_total_bonus_weight()
         );
-    }
 
-    #[test]
-    fn test_get_ft_factory_account_id() {
-        let expires_at = add_expires_at_nanos(100);
+        // 5.5, 5.25 and 5 NEAR of 15.75 NEAR of share weight
+        assert_eq!(349, contract.get_shares_of(&bob()));
+        assert_eq!(333, contract.get_shares_of(&carol()));
+        assert_eq!(317, contract.get_shares_of(&accounts(5)));
 
-        let contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+        let share = contract.get_share_fraction_of(&bob());
 
         assert_eq!(
-            accounts(4),
-            contract.get_ft_factory_account_id(),
-            "Recipient account id should be 'eugene.near'"
+            MIN_FUNDING_AMOUNT / 3 + MIN_FUNDING_AMOUNT / 30,
+            share.numerator.0
+        );
+        assert_eq!(
+            MIN_FUNDING_AMOUNT + contract.get_total_bonus_weight(),
+            share.denominator.0
         );
     }
 
     #[test]
-    fn test_get_dao_name() {
+    fn test_early_bonus_refund_returns_deposit() {
         let mut context = setup_context();
 
         let expires_at = add_expires_at_nanos(100);
 
-        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                early_bonus_basis_points: Some(1_000),
+                early_bonus_period: Some(EARLY_BONUS_PERIOD),
+                ..Default::default()
+            },
+        );
 
         testing_env!(context
-            .signer_account_id(bob())
-            .attached_deposit(MIN_FUNDING_AMOUNT / 2)
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
             .build());
 
         contract.deposit();
 
+        assert_eq!(ATTACHED_DEPOSIT / 10, contract.get_bonus_weight_of(&bob()));
+
         testing_env!(context
-            .signer_account_id(carol())
-            .attached_deposit(MIN_FUNDING_AMOUNT / 2)
+            .attached_deposit(0)
+            .block_timestamp(expires_at + 100)
             .build());
 
-        contract.deposit();
+        contract.withdraw();
 
-        testing_env!(context
-            .block_timestamp((expires_at + 200).try_into().unwrap())
-            .build());
+        let event = EscrowEvent::Withdraw(WithdrawEvent {
+            account_id: bob(),
+            amount: U128(ATTACHED_DEPOSIT),
+            balance: U128(0),
+            total_funds: U128(0),
+            unpaid_funding_amount: U128(MIN_FUNDING_AMOUNT),
+        });
 
-        contract.delegate_funds("dao1".to_string());
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        assert_eq!(0, contract.get_bonus_weight_of(&bob()));
+        assert_eq!(0, contract.get_total_bonus_weight());
 
         testing_env!(
-            context.build(),
+            context.predecessor_account_id(alice()).build(),
             near_sdk::VMConfig::test(),
             near_sdk::RuntimeFeesConfig::test(),
             Default::default(),
-            vec![
-                PromiseResult::Successful("true".to_string().into_bytes()),
-                PromiseResult::Successful("true".to_string().into_bytes())
-            ],
+            vec![PromiseResult::Failed],
         );
 
-        assert_eq!(
-            contract.on_delegate_callback("dao1".to_string()),
-            true,
-            "delegate_funds should run successfully"
+        contract.on_withdraw_callback(
+            bob(),
+            U128(ATTACHED_DEPOSIT),
+            U128(ATTACHED_DEPOSIT),
+            Some(U128(ATTACHED_DEPOSIT / 10)),
+            None,
+            None,
         );
 
-        assert_eq!("dao1", contract.get_dao_name(), "Should equal DAO Name");
+        assert_eq!(ATTACHED_DEPOSIT / 10, contract.get_bonus_weight_of(&bob()));
+        assert_eq!(ATTACHED_DEPOSIT / 10, contract.get_total_bonus_weight());
     }
 
     #[test]
-    fn test_get_metadata_url() {
-        let expires_at = add_expires_at_nanos(100);
+    #[should_panic(expected = "ERR_INVALID_EARLY_BONUS")]
+    fn test_invalid_early_bonus() {
+        setup_context();
 
-        let contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+        let expires_at = add_expires_at_nanos(100);
 
-        assert_eq!(
-            "metadata_url.json",
-            contract.get_metadata_url(),
-            "Contract was not initilialized with metadata_url param"
+        setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                early_bonus_basis_points: Some(MAX_EARLY_BONUS_BASIS_POINTS + 1),
+                early_bonus_period: Some(EARLY_BONUS_PERIOD),
+                ..Default::default()
+            },
         );
     }
 
     #[test]
-    fn test_get_0_total_funds() {
+    fn test_match_deposits_to_dao() {
+        let mut context = setup_context();
+
         let expires_at = add_expires_at_nanos(100);
 
-        let contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+        testing_env!(context.attached_deposit(ATTACHED_DEPOSIT * 2).build());
 
-        assert_eq!(0, contract.get_total_funds(), "Total funds should be 0");
-    }
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                cooling_off_period: Some(1_000_000),
+                sponsor_account_id: Some(accounts(5)),
+                is_match_in_shares: Some(false),
+                ..Default::default()
+            },
+        );
 
-    #[test]
-    fn test_get_correct_unpaid_funding_amount() {
-        let mut context = setup_context();
+        assert_eq!(ATTACHED_DEPOSIT * 2, contract.get_match_pool());
 
         testing_env!(context
-            .signer_account_id(bob())
+            .predecessor_account_id(bob())
             .attached_deposit(ATTACHED_DEPOSIT)
             .build());
 
-        let expires_at = add_expires_at_nanos(100);
+        contract.deposit();
 
-        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT * 3)
+            .build());
 
         contract.deposit();
 
-        assert_eq!(
-            MIN_FUNDING_AMOUNT - ATTACHED_DEPOSIT,
-            contract.get_unpaid_funding_amount(),
-            "Unpaid funding amount is wrong"
-        );
+        assert_eq!(ATTACHED_DEPOSIT, contract.get_match_of(&bob()));
+        assert_eq!(ATTACHED_DEPOSIT, contract.get_match_of(&carol()));
+        assert_eq!(0, contract.get_match_pool());
+        assert_eq!(ATTACHED_DEPOSIT * 2, contract.get_matched_funds());
+        assert_eq!(ATTACHED_DEPOSIT * 6, contract.get_total_funds());
+        assert_eq!(ATTACHED_DEPOSIT * 4, contract.get_raised_funds());
+
+        // The matched funds are not part of the shares
+        assert_eq!(250, contract.get_shares_of(&bob()));
+        assert_eq!(750, contract.get_shares_of(&carol()));
+        assert_eq!(0, contract.get_shares_of(&accounts(5)));
     }
 
     #[test]
-    fn test_get_total_funds_after_deposits() {
+    fn test_match_deposits_in_shares() {
         let mut context = setup_context();
 
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                cooling_off_period: Some(1_000_000),
+                sponsor_account_id: Some(accounts(5)),
+                is_match_in_shares: Some(true),
+                ..Default::default()
+            },
+        );
+
         testing_env!(context
-            .signer_account_id(bob())
-            .attached_deposit(ATTACHED_DEPOSIT)
+            .predecessor_account_id(accounts(5))
+            .attached_deposit(ATTACHED_DEPOSIT * 2)
             .build());
 
-        let expires_at = add_expires_at_nanos(100);
+        contract.fund_match();
 
-        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+        let event = EscrowEvent::MatchFunded(MatchFundedEvent {
+            sponsor_account_id: accounts(5),
+            amount: U128(ATTACHED_DEPOSIT * 2),
+            match_pool: U128(ATTACHED_DEPOSIT * 2),
+        });
 
-        contract.deposit();
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
 
         testing_env!(context
-            .signer_account_id(carol())
+            .predecessor_account_id(bob())
             .attached_deposit(ATTACHED_DEPOSIT)
             .build());
 
         contract.deposit();
 
-        assert_eq!(
-            ATTACHED_DEPOSIT * 2,
-            contract.get_total_funds(),
-            "Total funds should be ATTACHED_DEPOSITx2"
-        );
+        let event = EscrowEvent::DepositMatched(DepositMatchedEvent {
+            sponsor_account_id: accounts(5),
+            account_id: bob(),
+            amount: U128(ATTACHED_DEPOSIT),
+            match_pool: U128(ATTACHED_DEPOSIT),
+            total_funds: U128(ATTACHED_DEPOSIT * 2),
+        });
+
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+
+        assert_eq!(ATTACHED_DEPOSIT, contract.get_match_pool());
+        assert_eq!(ATTACHED_DEPOSIT, contract.deposits_of(&accounts(5)));
+        assert_eq!(ATTACHED_DEPOSIT * 2, contract.get_total_funds());
+        assert_eq!(ATTACHED_DEPOSIT * 2, contract.get_raised_funds());
+        assert_eq!(500, contract.get_shares_of(&bob()));
+        assert_eq!(500, contract.get_shares_of(&accounts(5)));
+
+        testing_env!(context.attached_deposit(0).build());
+
+        contract.withdraw_early();
+
+        assert_eq!(0, contract.get_match_of(&bob()));
+        assert_eq!(ATTACHED_DEPOSIT * 2, contract.get_match_pool());
+        assert_eq!(0, contract.deposits_of(&accounts(5)));
+        assert_eq!(0, contract.get_total_funds());
+        assert_eq!(0, contract.get_raised_funds());
     }
 
     #[test]
-    fn test_is_withdrawal_allowed() {
+    fn test_withdraw_match_after_expiration() {
         let mut context = setup_context();
 
         let expires_at = add_expires_at_nanos(100);
 
-        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT * 2);
+        testing_env!(context.attached_deposit(ATTACHED_DEPOSIT * 2).build());
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                cooling_off_period: Some(1_000_000),
+                sponsor_account_id: Some(accounts(5)),
+                is_match_in_shares: Some(false),
+                ..Default::default()
+            },
+        );
 
         testing_env!(context
-            .signer_account_id(bob())
-            .attached_deposit(MIN_FUNDING_AMOUNT - 1_000)
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
             .build());
 
         contract.deposit();
 
         testing_env!(context
-            .signer_account_id(carol())
-            .attached_deposit(MIN_FUNDING_AMOUNT - 1_000)
+            .predecessor_account_id(accounts(5))
+            .attached_deposit(0)
+            .block_timestamp(expires_at + 100)
             .build());
 
-        contract.deposit();
+        contract.withdraw_match();
 
-        testing_env!(context
-            .signer_account_id(bob())
-            .block_timestamp((expires_at + 100).try_into().unwrap())
-            .build());
+        let event = EscrowEvent::MatchWithdrawn(MatchWithdrawnEvent {
+            sponsor_account_id: accounts(5),
+            amount: U128(ATTACHED_DEPOSIT * 2),
+            matched_funds: U128(0),
+            total_funds: U128(ATTACHED_DEPOSIT),
+        });
 
-        contract.withdraw();
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
 
-        testing_env!(context.signer_account_id(carol()).build());
+        assert_eq!(0, contract.get_match_pool());
+        assert_eq!(0, contract.get_matched_funds());
+        assert_eq!(ATTACHED_DEPOSIT, contract.get_total_funds());
+        assert!(get_function_call_gas(Some("on_payout_callback")) > 0);
 
-        contract.withdraw();
+        // A failed transfer stays claimable by the sponsor
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
 
+        assert!(!contract.on_payout_callback(accounts(5), U128(ATTACHED_DEPOSIT * 2)));
         assert_eq!(
-            true,
-            contract.is_withdrawal_allowed(),
-            "Withdrawal should be allowed"
+            ATTACHED_DEPOSIT * 2,
+            contract.get_pending_payout_of(&accounts(5))
         );
 
-        assert_eq!(0, contract.get_total_funds(), "Total funds should be 0");
-    }
+        contract.withdraw_payout();
 
-    #[test]
-    #[should_panic(expected = "ERR_DEPOSIT_SHOULD_NOT_BE_0")]
-    fn test_deposits() {
-        let mut context = setup_context();
+        assert_eq!(0, contract.get_pending_payout_of(&accounts(5)));
 
-        testing_env!(context.signer_account_id(bob()).attached_deposit(0).build());
+        testing_env!(context.predecessor_account_id(bob()).build());
 
-        let expires_at = add_expires_at_nanos(100);
+        contract.withdraw();
 
-        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+        let event = EscrowEvent::Withdraw(WithdrawEvent {
+            account_id: bob(),
+            amount: U128(ATTACHED_DEPOSIT),
+            balance: U128(0),
+            total_funds: U128(0),
+            unpaid_funding_amount: U128(MIN_FUNDING_AMOUNT),
+        });
 
-        contract.deposit();
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
     }
 
     #[test]
     #[should_panic(expected = "ERR_WITHDRAWAL_NOT_ALLOWED")]
-    fn test_is_withdrawal_not_allowed() {
-        setup_context();
-        let expires_at = add_expires_at_nanos(1_000_000);
+    fn test_withdraw_match_during_funding() {
+        let mut context = setup_context();
 
-        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+        let expires_at = add_expires_at_nanos(100);
 
-        contract.withdraw();
+        testing_env!(context.attached_deposit(ATTACHED_DEPOSIT).build());
 
-        assert_eq!(
-            false,
-            contract.is_withdrawal_allowed(),
-            "Withdrawal should not be allowed"
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                cooling_off_period: Some(1_000_000),
+                sponsor_account_id: Some(accounts(5)),
+                is_match_in_shares: Some(false),
+                ..Default::default()
+            },
         );
+
+        testing_env!(context
+            .predecessor_account_id(accounts(5))
+            .attached_deposit(0)
+            .build());
+
+        contract.withdraw_match();
     }
 
     #[test]
-    #[should_panic(expected = "ERR_DEPOSIT_NOT_ALLOWED")]
-    fn test_is_deposit_not_allowed_by_expiration_date() {
+    #[should_panic(expected = "ERR_NOT_SPONSOR")]
+    fn test_fund_match_not_sponsor() {
         let mut context = setup_context();
 
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                cooling_off_period: Some(1_000_000),
+                sponsor_account_id: Some(accounts(5)),
+                is_match_in_shares: Some(false),
+                ..Default::default()
+            },
+        );
+
         testing_env!(context
-            .signer_account_id(bob())
+            .predecessor_account_id(bob())
             .attached_deposit(ATTACHED_DEPOSIT)
             .build());
 
-        let expires_at = substract_expires_at_nanos(5_000_000);
+        contract.fund_match();
+    }
 
-        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+    #[test]
+    fn test_attestation_registry_deposit_charges_storage_fee() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                attestation_registry_id: Some(accounts(0)),
+                is_storage_fee_charged: Some(true),
+                ..Default::default()
+            },
+        );
+
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
 
         contract.deposit();
 
+        // The storage fee is charged when the deposit is recorded
+        assert_eq!(0, contract.get_storage_fee_of(&bob()));
+
+        testing_env!(
+            context
+                .predecessor_account_id(alice())
+                .attached_deposit(0)
+                .build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful("true".to_string().into_bytes())],
+        );
+
         assert_eq!(
-            false,
-            contract.is_deposit_allowed(),
-            "Deposit should not be allowed"
+            U128(0),
+            contract.on_verify_deposit_callback(bob(), U128(ATTACHED_DEPOSIT), bob())
         );
+
+        let storage_fee = contract.get_storage_fee_of(&bob());
+
+        assert!(storage_fee > 0);
+        assert_eq!(ATTACHED_DEPOSIT - storage_fee, contract.deposits_of(&bob()));
     }
 
     #[test]
-    #[should_panic(expected = "ERR_DEPOSIT_NOT_ALLOWED")]
-    fn test_is_deposit_not_allowed_by_total_funds_reached() {
+    #[should_panic(expected = "ERR_INSUFFICIENT_STORAGE_FEE")]
+    fn test_attestation_registry_deposit_below_storage_fee() {
         let mut context = setup_context();
 
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                attestation_registry_id: Some(accounts(0)),
+                is_storage_fee_charged: Some(true),
+                ..Default::default()
+            },
+        );
+
         testing_env!(context
-            .signer_account_id(bob())
-            .attached_deposit(MIN_FUNDING_AMOUNT)
+            .predecessor_account_id(bob())
+            .attached_deposit(1)
             .build());
 
-        let expires_at = add_expires_at_nanos(1_000_000);
+        contract.deposit();
+    }
 
-        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+    #[test]
+    fn test_verify_deposit_callback_refunds_failed_deposit() {
+        let mut context = setup_context();
 
-        contract.deposit();
+        let expires_at = add_expires_at_nanos(100);
 
-        testing_env!(context
-            .signer_account_id(carol())
-            .attached_deposit(ATTACHED_DEPOSIT)
-            .build());
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                attestation_registry_id: Some(accounts(0)),
+                is_storage_fee_charged: Some(true),
+                ..Default::default()
+            },
+        );
 
-        contract.deposit();
+        testing_env!(
+            context.predecessor_account_id(alice()).build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful("true".to_string().into_bytes())],
+        );
 
+        // Verified, but it does not cover the storage fee
         assert_eq!(
-            false,
-            contract.is_deposit_allowed(),
-            "Deposit should not be allowed"
+            U128(1),
+            contract.on_verify_deposit_callback(bob(), U128(1), bob())
         );
+
+        assert!(get_logs()
+            .contains(&"ERR_INSUFFICIENT_STORAGE_FEE: bob.near deposit refunded".to_string()));
+        assert_eq!(0, contract.deposits_of(&bob()));
+        assert_eq!(0, contract.get_storage_fee_of(&bob()));
+        assert_eq!(0, contract.get_total_storage_fees());
+        assert_eq!(0, contract.get_depositors_count());
     }
 
     #[test]
-    #[should_panic(expected = "ERR_OWNER_SHOULD_NOT_DEPOSIT")]
-    fn test_owner_deposit() {
+    fn test_transfer_position_clamps_cooling_off_amount() {
         let mut context = setup_context();
 
         let expires_at = add_expires_at_nanos(100);
 
-        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                cooling_off_period: Some(1_000),
+                early_exit_penalty_basis_points: Some(1_000),
+                is_early_exit_penalty_to_creator: Some(false),
+                ..Default::default()
+            },
+        );
 
         testing_env!(context
-            .signer_account_id(alice())
-            .attached_deposit(ATTACHED_DEPOSIT)
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 5)
+            .block_timestamp(1_000)
             .build());
 
         contract.deposit();
-    }
 
-    #[test]
-    #[should_panic(expected = "ERR_DELEGATE_NOT_ALLOWED")]
-    fn test_should_not_delegate_funds_if_active() {
-        let mut context = setup_context();
+        testing_env!(context
+            .predecessor_account_id(carol())
+            .block_timestamp(3_000)
+            .build());
 
-        let expires_at = add_expires_at_nanos(100);
+        contract.deposit();
 
-        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+        testing_env!(context.attached_deposit(0).block_timestamp(3_100).build());
 
+        contract.transfer_position(accounts(2), U128(ATTACHED_DEPOSIT * 5));
+
+        // Bob's position is out of its cooling-off period, it stays penalized with carol
         testing_env!(context
-            .signer_account_id(bob())
-            .attached_deposit(ATTACHED_DEPOSIT)
+            .predecessor_account_id(bob())
+            .block_timestamp(3_200)
             .build());
 
-        contract.deposit();
+        contract.transfer_position(carol(), U128(ATTACHED_DEPOSIT * 5));
 
         assert_eq!(
-            true,
-            contract.is_deposit_allowed(),
-            "Deposit should be allowed"
+            Some(ATTACHED_DEPOSIT / 2),
+            contract.get_early_exit_penalty_of(&carol())
         );
-
+        // Received positions are not in the cooling-off period of the receiver
         assert_eq!(
-            false,
-            contract.is_withdrawal_allowed(),
-            "Withdrawal should not be allowed"
+            Some(ATTACHED_DEPOSIT / 2),
+            contract.get_early_exit_penalty_of(&accounts(2))
         );
-
-        contract.delegate_funds("dao1".to_string());
     }
 
     #[test]
-    #[should_panic(expected = "ERR_DELEGATE_NOT_ALLOWED")]
-    fn test_should_not_delegate_funds_if_expired() {
+    fn test_withdraw_early_failure_keeps_new_deposits() {
         let mut context = setup_context();
 
         let expires_at = add_expires_at_nanos(100);
 
-        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                cooling_off_period: Some(1_000),
+                early_exit_penalty_basis_points: Some(1_000),
+                is_early_exit_penalty_to_creator: Some(false),
+                ..Default::default()
+            },
+        );
 
         testing_env!(context
-            .signer_account_id(bob())
-            .attached_deposit(MIN_FUNDING_AMOUNT - 1_000)
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 2)
+            .block_timestamp(1_000)
             .build());
 
         contract.deposit();
 
-        testing_env!(context
-            .block_timestamp((expires_at + 200).try_into().unwrap())
-            .build());
+        testing_env!(context.attached_deposit(0).block_timestamp(1_500).build());
 
-        assert_eq!(
-            false,
-            contract.is_deposit_allowed(),
-            "Deposit should not be allowed"
+        contract.withdraw_early();
+
+        testing_env!(context.attached_deposit(ATTACHED_DEPOSIT).build());
+
+        contract.deposit();
+
+        testing_env!(
+            context
+                .predecessor_account_id(alice())
+                .attached_deposit(0)
+                .build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
         );
 
+        assert!(!contract.on_withdraw_callback(
+            bob(),
+            U128(ATTACHED_DEPOSIT * 2),
+            U128(ATTACHED_DEPOSIT * 2),
+            None,
+            None,
+            Some(vec![(2_000, U128(ATTACHED_DEPOSIT * 2))])
+        ));
+
+        assert_eq!(ATTACHED_DEPOSIT * 3, contract.deposits_of(&bob()));
+        assert_eq!(ATTACHED_DEPOSIT * 3, contract.get_total_funds());
+        assert_eq!(ATTACHED_DEPOSIT * 3, contract.get_raised_funds());
         assert_eq!(
-            true,
-            contract.is_withdrawal_allowed(),
-            "Withdrawal should be allowed"
+            ATTACHED_DEPOSIT * 3,
+            contract.get_cooling_off_amount_of(&bob())
         );
-
-        contract.delegate_funds("dao1".to_string());
     }
 
     #[test]
-    #[should_panic(expected = "ERR_DELEGATE_NOT_ALLOWED")]
-    fn test_should_not_delegate_funds_if_already_delegated() {
+    fn test_withdraw_early_failure_restores_cooling_off_and_storage_fee() {
         let mut context = setup_context();
 
         let expires_at = add_expires_at_nanos(100);
 
-        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                cooling_off_period: Some(1_000),
+                early_exit_penalty_basis_points: Some(1_000),
+                is_storage_fee_charged: Some(true),
+                ..Default::default()
+            },
+        );
 
         testing_env!(context
-            .signer_account_id(bob())
-            .attached_deposit(MIN_FUNDING_AMOUNT / 2)
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .block_timestamp(1_000)
             .build());
 
         contract.deposit();
 
-        testing_env!(context
-            .signer_account_id(carol())
-            .attached_deposit(MIN_FUNDING_AMOUNT / 2)
-            .build());
+        let storage_fee = contract.get_storage_fee_of(&bob());
+        let balance = contract.deposits_of(&bob());
 
-        contract.deposit();
+        testing_env!(context.attached_deposit(0).block_timestamp(1_500).build());
 
-        testing_env!(context
-            .block_timestamp((expires_at + 200).try_into().unwrap())
-            .build());
+        contract.withdraw_early();
 
-        assert_eq!(
-            false,
-            contract.is_deposit_allowed(),
-            "Deposit should not be allowed"
+        assert_eq!(0, contract.get_storage_fee_of(&bob()));
+        assert_eq!(0, contract.get_cooling_off_amount_of(&bob()));
+
+        testing_env!(
+            context.predecessor_account_id(alice()).build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
         );
 
-        assert_eq!(
-            false,
-            contract.is_withdrawal_allowed(),
-            "Withdrawal should not be allowed"
+        assert!(!contract.on_withdraw_callback(
+            bob(),
+            U128(balance),
+            U128(balance),
+            None,
+            Some(U128(storage_fee)),
+            Some(vec![(2_000, U128(balance))])
+        ));
+
+        assert_eq!(balance, contract.deposits_of(&bob()));
+        assert_eq!(storage_fee, contract.get_storage_fee_of(&bob()));
+        assert_eq!(storage_fee, contract.get_total_storage_fees());
+        assert_eq!(balance, contract.get_cooling_off_amount_of(&bob()));
+
+        // The retry is still within the cooling-off period and is not charged the penalty
+        assert_eq!(Some(0), contract.get_early_exit_penalty_of(&bob()));
+    }
+
+    fn setup_failed_token_delegation(
+        context: &mut VMContextBuilder,
+        expires_at: u64,
+    ) -> ConditionalEscrow {
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                funding_token_id: Some(accounts(5)),
+                ..Default::default()
+            },
         );
 
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+
+        contract.ft_on_transfer(bob(), U128(MIN_FUNDING_AMOUNT), "".to_string());
+
+        testing_env!(context
+            .predecessor_account_id(alice())
+            .block_timestamp(expires_at + 200)
+            .attached_deposit(FT_ATTACHED_DEPOSIT * 2)
+            .build());
+
         contract.delegate_funds("dao1".to_string());
 
         testing_env!(
-            context.build(),
+            context.attached_deposit(0).build(),
             near_sdk::VMConfig::test(),
             near_sdk::RuntimeFeesConfig::test(),
             Default::default(),
             vec![
-                PromiseResult::Successful("true".to_string().into_bytes()),
+                PromiseResult::Failed,
                 PromiseResult::Successful("true".to_string().into_bytes())
             ],
         );
 
-        assert_eq!(
-            contract.on_delegate_callback("dao1".to_string()),
-            true,
-            "delegate_funds should run successfully"
-        );
-
-        assert_eq!(0, contract.get_total_funds(), "Total funds should be 0");
+        assert!(!contract.on_delegate_callback("dao1".to_string()));
 
-        contract.delegate_funds("dao1".to_string());
+        contract
     }
 
     #[test]
-    #[should_panic(expected = "ERR_CREATE_DAO_UNSUCCESSFUL")]
-    fn test_should_not_delegate_funds_if_create_dao_fails() {
+    fn test_ft_delegate_funds_retry_reuses_keeper_refund() {
         let mut context = setup_context();
 
         let expires_at = add_expires_at_nanos(100);
 
-        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+        let mut contract = setup_failed_token_delegation(&mut context, expires_at);
 
-        testing_env!(context
-            .signer_account_id(bob())
-            .attached_deposit(MIN_FUNDING_AMOUNT / 2)
-            .build());
+        assert_eq!(FT_ATTACHED_DEPOSIT, contract.get_keeper_refund_of(&alice()));
 
-        contract.deposit();
+        // The refunded create_dao deposit covers the retry
+        contract.delegate_funds("dao2".to_string());
 
-        testing_env!(context
-            .signer_account_id(carol())
-            .attached_deposit(MIN_FUNDING_AMOUNT / 2)
-            .build());
+        assert_eq!(0, contract.get_keeper_refund_of(&alice()));
 
-        contract.deposit();
+        let event = EscrowEvent::DelegateStarted(DelegateStartedEvent {
+            dao_name: "dao2".to_string(),
+            dao_amount: U128(FT_ATTACHED_DEPOSIT),
+            ft_amount: U128(0),
+            fee_amount: U128(0),
+            depositors_count: 1,
+        });
 
-        testing_env!(context
-            .block_timestamp((expires_at + 200).try_into().unwrap())
-            .build());
+        assert!(get_logs().contains(&format!("EVENT_JSON:{}", event.to_json_string())));
+    }
 
-        assert_eq!(
-            false,
-            contract.is_deposit_allowed(),
-            "Deposit should not be allowed"
-        );
+    #[test]
+    fn test_withdraw_keeper_refund() {
+        let mut context = setup_context();
 
-        assert_eq!(
-            false,
-            contract.is_withdrawal_allowed(),
-            "Withdrawal should not be allowed"
-        );
+        let expires_at = add_expires_at_nanos(100);
 
-        contract.delegate_funds("dao1".to_string());
+        let mut contract = setup_failed_token_delegation(&mut context, expires_at);
+
+        contract.withdraw_keeper_refund();
+
+        assert_eq!(0, contract.get_keeper_refund_of(&alice()));
 
         testing_env!(
             context.build(),
             near_sdk::VMConfig::test(),
             near_sdk::RuntimeFeesConfig::test(),
             Default::default(),
-            vec![PromiseResult::Failed, PromiseResult::Failed],
+            vec![PromiseResult::Failed],
         );
 
-        assert_eq!(
-            contract.on_delegate_callback("dao1".to_string()),
-            false,
-            "delegate_funds should fail"
-        );
+        // A failed transfer keeps the refund withdrawable
+        assert!(!contract.on_keeper_refund_callback(alice(), U128(FT_ATTACHED_DEPOSIT)));
+        assert_eq!(FT_ATTACHED_DEPOSIT, contract.get_keeper_refund_of(&alice()));
+    }
 
-        assert_eq!(
-            MIN_FUNDING_AMOUNT,
-            contract.get_total_funds(),
-            "Total funds should be MIN_FUNDING_AMOUNT"
-        );
+    #[test]
+    #[should_panic(expected = "ERR_WITHDRAWAL_NOT_ALLOWED")]
+    fn test_should_not_withdraw_keeper_refund_of_other_account() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_failed_token_delegation(&mut context, expires_at);
+
+        testing_env!(context.predecessor_account_id(bob()).build());
+
+        contract.withdraw_keeper_refund();
     }
 
     #[test]
-    fn test_delegate_funds() {
+    fn test_get_campaign_summary_largest_deposit() {
         let mut context = setup_context();
 
         let expires_at = add_expires_at_nanos(100);
 
-        let mut contract = setup_contract(expires_at, MIN_FUNDING_AMOUNT);
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                is_position_transfer_approval_required: Some(false),
+                ..Default::default()
+            },
+        );
 
         testing_env!(context
-            .signer_account_id(bob())
-            .attached_deposit(MIN_FUNDING_AMOUNT / 2)
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 3)
             .build());
 
         contract.deposit();
 
-        testing_env!(context
-            .signer_account_id(carol())
-            .attached_deposit(MIN_FUNDING_AMOUNT / 2)
-            .build());
+        testing_env!(context.attached_deposit(0).build());
 
-        contract.deposit();
+        let summary = contract.get_campaign_summary();
+
+        assert_eq!(Some(bob()), summary.largest_depositor);
+        assert_eq!(ATTACHED_DEPOSIT * 3, summary.largest_deposit.0);
+
+        contract.transfer_position(carol(), U128(ATTACHED_DEPOSIT));
+        contract.transfer_position(accounts(3), U128(ATTACHED_DEPOSIT * 2));
+
+        let summary = contract.get_campaign_summary();
+
+        assert_eq!(Some(accounts(3)), summary.largest_depositor);
+        assert_eq!(ATTACHED_DEPOSIT * 2, summary.largest_deposit.0);
 
         testing_env!(context
-            .block_timestamp((expires_at + 200).try_into().unwrap())
+            .predecessor_account_id(accounts(3))
+            .block_timestamp(expires_at + 100)
             .build());
 
-        assert_eq!(
-            false,
-            contract.is_deposit_allowed(),
-            "Deposit should not be allowed"
-        );
+        contract.withdraw();
 
-        assert_eq!(
-            false,
-            contract.is_withdrawal_allowed(),
-            "Withdrawal should not be allowed"
+        let summary = contract.get_campaign_summary();
+
+        assert_eq!(Some(carol()), summary.largest_depositor);
+        assert_eq!(ATTACHED_DEPOSIT, summary.largest_deposit.0);
+
+        testing_env!(context.predecessor_account_id(carol()).build());
+
+        contract.withdraw();
+
+        let summary = contract.get_campaign_summary();
+
+        assert_eq!(None, summary.largest_depositor);
+        assert_eq!(0, summary.largest_deposit.0);
+    }
+
+    fn get_function_call_gas(method_name: Option<&str>) -> u64 {
+        near_sdk::test_utils::get_created_receipts()
+            .iter()
+            .flat_map(|receipt| receipt.actions.iter())
+            .map(|action| match action {
+                near_sdk::mock::VmAction::FunctionCall {
+                    function_name, gas, ..
+                } if method_name.is_none() || method_name == Some(function_name.as_str()) => gas.0,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_ft_delegate_callback_gas_covers_transfers() {
+        let mut context = setup_context();
+
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                funding_token_id: Some(accounts(5)),
+                fee_basis_points: Some(FEE_BASIS_POINTS),
+                fee_account_id: Some("fees.near".parse().unwrap()),
+                keeper_reward_basis_points: Some(50),
+                ..Default::default()
+            },
         );
 
+        testing_env!(context.predecessor_account_id(accounts(5)).build());
+
+        contract.ft_on_transfer(bob(), U128(MIN_FUNDING_AMOUNT), "".to_string());
+
+        testing_env!(context
+            .predecessor_account_id(alice())
+            .block_timestamp(expires_at + 200)
+            .attached_deposit(FT_ATTACHED_DEPOSIT * 2)
+            .build());
+
         contract.delegate_funds("dao1".to_string());
 
+        let callback_gas = get_function_call_gas(Some("on_delegate_callback"));
+
         testing_env!(
-            context.build(),
+            context.attached_deposit(0).build(),
             near_sdk::VMConfig::test(),
             near_sdk::RuntimeFeesConfig::test(),
             Default::default(),
@@ -978,24 +10221,61 @@ mod tests {
             ],
         );
 
+        assert!(contract.on_delegate_callback("dao1".to_string()));
+
+        // The fee and keeper reward ft_transfer calls, their callbacks and the forward_funds calls
         assert_eq!(
-            contract.on_delegate_callback("dao1".to_string()),
-            true,
-            "delegate_funds should run successfully"
+            GAS_FOR_FT_TRANSFER.0 * 3
+                + GAS_FOR_PAYOUT_CALLBACK.0 * 2
+                + GAS_FOR_FT_TRANSFER_CALLBACK.0 * 2
+                + GAS_FOR_GET_DAO_ACCOUNT.0,
+            get_function_call_gas(None)
         );
+        assert!(get_function_call_gas(None) + GAS_FOR_DELEGATE_CALLBACK.0 <= callback_gas);
+    }
 
-        assert_eq!(0, contract.get_total_funds(), "Total funds should be 0");
+    #[test]
+    fn test_top_up_does_not_extend_cooling_off_of_earlier_deposits() {
+        let mut context = setup_context();
 
-        assert_eq!(
-            MIN_FUNDING_AMOUNT / 2,
-            contract.deposits_of(&bob()),
-            "Account deposits should be MIN_FUNDING_AMOUNT"
+        let expires_at = add_expires_at_nanos(100);
+
+        let mut contract = setup_contract_with(
+            expires_at,
+            MIN_FUNDING_AMOUNT,
+            CampaignConfig {
+                creator_account_id: Some(accounts(5)),
+                cooling_off_period: Some(1_000),
+                early_exit_penalty_basis_points: Some(1_000),
+                is_early_exit_penalty_to_creator: Some(false),
+                ..Default::default()
+            },
         );
 
+        testing_env!(context
+            .predecessor_account_id(bob())
+            .attached_deposit(ATTACHED_DEPOSIT * 5)
+            .block_timestamp(1_000)
+            .build());
+
+        contract.deposit();
+
+        // Top-ups right before each cooling-off period ends
+        for timestamp in [1_900, 2_800, 3_700] {
+            testing_env!(context
+                .attached_deposit(1)
+                .block_timestamp(timestamp)
+                .build());
+
+            contract.deposit();
+        }
+
+        testing_env!(context.attached_deposit(0).block_timestamp(3_800).build());
+
+        assert_eq!(2, contract.get_cooling_off_amount_of(&bob()));
         assert_eq!(
-            MIN_FUNDING_AMOUNT / 2,
-            contract.deposits_of(&carol()),
-            "Account deposits should be MIN_FUNDING_AMOUNT"
+            Some(ATTACHED_DEPOSIT / 2),
+            contract.get_early_exit_penalty_of(&bob())
         );
     }
 }
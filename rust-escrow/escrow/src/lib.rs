@@ -1,6 +1,10 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::collections::LookupMap;
-use near_sdk::{env, log, near_bindgen, AccountId, Balance, Promise};
+use near_sdk::json_types::U128;
+use near_sdk::serde_json::json;
+use near_sdk::{env, log, near_bindgen, AccountId, Balance, Gas, Promise};
+
+pub const GAS_FOR_WITHDRAW_CALLBACK: Gas = Gas(5_000_000_000_000);
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
@@ -61,7 +65,21 @@ impl Escrow {
         let payee = env::signer_account_id();
         let payment = self.deposits_of(&payee);
 
-        Promise::new(payee.clone()).transfer(payment);
+        if payment == 0 {
+            env::panic_str("ERR_ZERO_BALANCE");
+        }
+
+        Promise::new(payee.clone()).transfer(payment).then(
+            Promise::new(env::current_account_id()).function_call(
+                "on_withdraw_callback".to_string(),
+                json!({"payee": payee, "amount": U128(payment)})
+                    .to_string()
+                    .into_bytes(),
+                0,
+                GAS_FOR_WITHDRAW_CALLBACK,
+            ),
+        );
+
         self.deposits.insert(&payee, &0);
 
         log!(
@@ -72,14 +90,41 @@ impl Escrow {
         );
         // @TODO emit withdraw event
     }
+
+    /// Restores the payee balance if the transfer of a withdrawal failed
+    #[private]
+    pub fn on_withdraw_callback(&mut self, payee: AccountId, amount: U128) -> bool {
+        if near_sdk::is_promise_success() {
+            log!(
+                "{} withdrawal of {} NEAR tokens succeeded",
+                &payee,
+                amount.0
+            );
+
+            return true;
+        }
+
+        let new_balance = self.deposits_of(&payee) + amount.0;
+
+        self.deposits.insert(&payee, &new_balance);
+
+        log!(
+            "{} withdrawal of {} NEAR tokens failed. Balance restored to {}",
+            &payee,
+            amount.0,
+            new_balance
+        );
+
+        false
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use near_sdk::test_utils::test_env::{alice, bob, carol};
-    use near_sdk::test_utils::VMContextBuilder;
-    use near_sdk::testing_env;
+    use near_sdk::test_utils::{get_logs, VMContextBuilder};
+    use near_sdk::{testing_env, PromiseResult};
 
     const ATTACHED_DEPOSIT: Balance = 8540000000000000000000;
 
@@ -156,4 +201,82 @@ mod tests {
             "Account deposits should equal 0"
         );
     }
+
+    #[test]
+    #[should_panic(expected = "ERR_ZERO_BALANCE")]
+    fn test_withdraw_zero_balance() {
+        let (mut context, mut contract) = setup_contract();
+
+        testing_env!(context.signer_account_id(carol()).build());
+
+        contract.withdraw();
+    }
+
+    #[test]
+    fn test_withdraw_restores_balance_on_failure() {
+        let (mut context, mut contract) = setup_contract();
+
+        testing_env!(context
+            .signer_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+        contract.withdraw();
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+
+        assert!(!contract.on_withdraw_callback(carol(), U128(ATTACHED_DEPOSIT)));
+
+        assert_eq!(
+            ATTACHED_DEPOSIT,
+            contract.deposits_of(&carol()),
+            "Account deposits should be restored"
+        );
+
+        assert_eq!(
+            get_logs(),
+            vec![format!(
+                "{} withdrawal of {} NEAR tokens failed. Balance restored to {}",
+                carol(),
+                ATTACHED_DEPOSIT,
+                ATTACHED_DEPOSIT
+            )]
+        );
+    }
+
+    #[test]
+    fn test_withdraw_callback_success() {
+        let (mut context, mut contract) = setup_contract();
+
+        testing_env!(context
+            .signer_account_id(carol())
+            .attached_deposit(ATTACHED_DEPOSIT)
+            .build());
+
+        contract.deposit();
+        contract.withdraw();
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+
+        assert!(contract.on_withdraw_callback(carol(), U128(ATTACHED_DEPOSIT)));
+
+        assert_eq!(
+            0,
+            contract.deposits_of(&carol()),
+            "Account deposits should be 0"
+        );
+    }
 }
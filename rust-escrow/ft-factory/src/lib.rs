@@ -47,12 +47,16 @@ impl FtFactory {
         }
     }
 
+    /// Creates the FT of the calling escrow, treasury_account_id gets the rounding dust of the
+    /// claims and defaults to the escrow
     #[payable]
-    pub fn create_ft(&mut self, name: String) -> Promise {
+    pub fn create_ft(&mut self, name: String, treasury_account_id: Option<AccountId>) -> Promise {
         let ft_account_id: AccountId = format!("{}.{}", name, env::current_account_id())
             .parse()
             .unwrap();
         let predecessor_account_id = env::predecessor_account_id();
+        let treasury_account_id =
+            treasury_account_id.unwrap_or_else(|| predecessor_account_id.clone());
         let symbol = format!("NHA{}", self.ft_index.len() + 1);
 
         let promise = Promise::new(ft_account_id.clone())
@@ -62,7 +66,7 @@ impl FtFactory {
             .deploy_contract(FT_CODE.to_vec())
             .function_call(
                 "new".to_string(),
-                json!({"max_supply": FT_SUPPLY.to_string(), "escrow_account_id": predecessor_account_id, "metadata": { "spec": "ft-1.0.0", "name": name, "symbol": symbol, "decimals": 8 }})
+                json!({"max_supply": FT_SUPPLY.to_string(), "escrow_account_id": predecessor_account_id, "treasury_account_id": treasury_account_id, "metadata": { "spec": "ft-1.0.0", "name": name, "symbol": symbol, "decimals": 8 }})
                     .to_string()
                     .into_bytes(),
                 0,
@@ -154,7 +158,7 @@ mod tests {
             .build());
 
         let ft_name = "ft1".to_string();
-        contract.create_ft(ft_name.clone());
+        contract.create_ft(ft_name.clone(), None);
 
         testing_env!(
             context.build(),
@@ -188,7 +192,7 @@ mod tests {
 
         let ft_name = "ft2".to_string();
 
-        contract.create_ft(ft_name.clone());
+        contract.create_ft(ft_name.clone(), Some(bob()));
 
         testing_env!(
             context.build(),
@@ -227,7 +231,7 @@ mod tests {
             .build());
 
         let ft_name = "ft1".to_string();
-        contract.create_ft(ft_name.clone());
+        contract.create_ft(ft_name.clone(), None);
 
         testing_env!(
             context.build(),
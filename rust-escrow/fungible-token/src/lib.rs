@@ -3,23 +3,49 @@ use near_contract_standards::fungible_token::metadata::{
 };
 use near_contract_standards::fungible_token::FungibleToken;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::LazyOption;
+use near_sdk::collections::{LazyOption, LookupSet};
 use near_sdk::json_types::U128;
+use near_sdk::serde::Deserialize;
 use near_sdk::serde_json::json;
 use near_sdk::{
-    env, near_bindgen, AccountId, Gas, PanicOnDefault, Promise, PromiseOrValue, PromiseResult,
+    env, near_bindgen, AccountId, Balance, Gas, PanicOnDefault, Promise, PromiseOrValue,
+    PromiseResult,
 };
 
+// Lints triggered by the code that construct_uint generates
+#[allow(clippy::assign_op_pattern, clippy::manual_div_ceil)]
+mod math {
+    uint::construct_uint! {
+        /// 256-bit unsigned integer for intermediate share calculations
+        pub struct U256(4);
+    }
+}
+use crate::math::U256;
+
 // Amount of gas used
 pub const GAS_FOR_ESCROW_CALL: Gas = Gas(5_000_000_000_000);
 pub const GAS_FOR_CLAIM_CALLBACK: Gas = Gas(5_000_000_000_000);
 
+/// Exact share of a depositor as returned by the escrow get_share_fraction_of
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ShareFraction {
+    pub numerator: U128,
+    pub denominator: U128,
+}
+
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct Ft {
     // Max supply of the token
     max_supply: U128,
     escrow_account_id: AccountId,
+    // Receives the rounding dust of the claims
+    treasury_account_id: AccountId,
+    // Sum of the share numerators claimed so far
+    claimed_shares: Balance,
+    // Accounts that claimed their share, the treasury may hold tokens before its own claim
+    claimed_accounts: LookupSet<AccountId>,
     token: FungibleToken,
     metadata: LazyOption<FungibleTokenMetadata>,
 }
@@ -31,6 +57,7 @@ impl Ft {
     pub fn new(
         max_supply: U128,
         escrow_account_id: AccountId,
+        treasury_account_id: AccountId,
         metadata: FungibleTokenMetadata,
     ) -> Self {
         if env::state_exists() {
@@ -41,6 +68,9 @@ impl Ft {
         Self {
             max_supply,
             escrow_account_id,
+            treasury_account_id,
+            claimed_shares: 0,
+            claimed_accounts: LookupSet::new(b"c".to_vec()),
             token: FungibleToken::new(b"a".to_vec()),
             metadata: LazyOption::new(b"m".to_vec(), Some(&metadata)),
         }
@@ -54,15 +84,22 @@ impl Ft {
         self.escrow_account_id.clone()
     }
 
+    pub fn ft_treasury_account_id(&self) -> AccountId {
+        self.treasury_account_id.clone()
+    }
+
+    /// Claims the tokens of the share of the caller in the escrow
     pub fn claim(&mut self) -> Promise {
-        if self.token.accounts.get(&env::signer_account_id()).is_some() {
+        let account_id = env::predecessor_account_id();
+
+        if self.claimed_accounts.contains(&account_id) {
             env::panic_str("ERR_ALREADY_CLAIMED_TOKENS");
         }
 
         // Get balances
         let promise = Promise::new(self.escrow_account_id.clone()).function_call(
-            "get_shares_of".to_string(),
-            json!({"payee": env::signer_account_id().to_string()})
+            "get_share_fraction_of".to_string(),
+            json!({"payee": account_id.to_string()})
                 .to_string()
                 .into_bytes(),
             0,
@@ -72,7 +109,7 @@ impl Ft {
         let callback = Promise::new(env::current_account_id()) // the recipient of this ActionReceipt (&self)
             .function_call(
                 "on_claim_callback".to_string(), // the function call will be a callback function
-                vec![],                          // method arguments
+                json!({ "account_id": account_id }).to_string().into_bytes(), // method arguments
                 0,                               // amount of yoctoNEAR to attach
                 GAS_FOR_CLAIM_CALLBACK,          // gas to attach
             );
@@ -81,16 +118,30 @@ impl Ft {
     }
 
     #[private]
-    pub fn on_claim_callback(&mut self) {
+    pub fn on_claim_callback(&mut self, account_id: AccountId) {
         match env::promise_result(0) {
             PromiseResult::Successful(result) => {
-                let proportion: u128 = near_sdk::serde_json::from_slice(&result).unwrap();
+                let share: ShareFraction = near_sdk::serde_json::from_slice(&result).unwrap();
 
-                if proportion == 0 {
+                if share.numerator.0 == 0 {
                     env::panic_str("ERR_NO_TOKENS_TO_CLAIM");
                 }
 
-                let amount = self.max_supply.0 * proportion / 1000;
+                if !self.claimed_accounts.insert(&account_id) {
+                    env::panic_str("ERR_ALREADY_CLAIMED_TOKENS");
+                }
+
+                let claimed_shares = self.claimed_shares + share.numerator.0;
+
+                if claimed_shares > share.denominator.0 {
+                    env::panic_str("ERR_MAX_SUPPLY_EXCEEDED");
+                }
+
+                self.claimed_shares = claimed_shares;
+
+                let amount = (U256::from(self.max_supply.0) * U256::from(share.numerator.0)
+                    / U256::from(share.denominator.0))
+                .as_u128();
 
                 if let Some(new_total) = self.token.total_supply.checked_add(amount) {
                     if new_total > self.max_supply.0 {
@@ -100,14 +151,34 @@ impl Ft {
                     env::panic_str("ERR_TOTAL_SUPPLY_OVERFLOW");
                 }
 
-                self.token
-                    .internal_register_account(&env::signer_account_id());
-                self.token
-                    .internal_deposit(&env::signer_account_id(), amount);
+                self.mint(&account_id, amount);
+
+                // The treasury gets the rounding dust of each claim, so that the minted tokens
+                // always match the claimed shares and add up to the max supply once all are claimed
+                let claimed_supply = (U256::from(self.max_supply.0) * U256::from(claimed_shares)
+                    / U256::from(share.denominator.0))
+                .as_u128();
+
+                self.mint(
+                    &self.treasury_account_id.clone(),
+                    claimed_supply - self.token.total_supply,
+                );
             }
             _ => env::panic_str("ERR_CALLING_ESCROW_CONTRACT"),
         }
     }
+
+    fn mint(&mut self, account_id: &AccountId, amount: Balance) {
+        if amount == 0 {
+            return;
+        }
+
+        if !self.token.accounts.contains_key(account_id) {
+            self.token.internal_register_account(account_id);
+        }
+
+        self.token.internal_deposit(account_id, amount);
+    }
 }
 
 near_contract_standards::impl_fungible_token_core!(Ft, token);
@@ -129,6 +200,12 @@ mod tests {
 
     const MAX_SUPPLY: Balance = 1_000_000_000_000_000;
 
+    fn get_share(numerator: u128, denominator: u128) -> Vec<u8> {
+        json!({"numerator": U128(numerator), "denominator": U128(denominator)})
+            .to_string()
+            .into_bytes()
+    }
+
     fn get_metadata() -> FungibleTokenMetadata {
         FungibleTokenMetadata {
             spec: "ft-1.0.0".to_string(),
@@ -154,7 +231,12 @@ mod tests {
     fn test_new() {
         let mut context = get_context(accounts(1));
         testing_env!(context.build());
-        let contract = Ft::new(MAX_SUPPLY.into(), accounts(1).into(), get_metadata());
+        let contract = Ft::new(
+            MAX_SUPPLY.into(),
+            accounts(1).into(),
+            accounts(4),
+            get_metadata(),
+        );
         testing_env!(context.is_view(true).build());
 
         assert_eq!(contract.ft_max_supply().0, MAX_SUPPLY);
@@ -174,7 +256,12 @@ mod tests {
     fn test_ft_max_supply() {
         let context = get_context(accounts(1));
         testing_env!(context.build());
-        let contract = Ft::new(MAX_SUPPLY.into(), accounts(1).into(), get_metadata());
+        let contract = Ft::new(
+            MAX_SUPPLY.into(),
+            accounts(1).into(),
+            accounts(4),
+            get_metadata(),
+        );
 
         assert_eq!(contract.ft_max_supply().0, MAX_SUPPLY);
     }
@@ -183,17 +270,28 @@ mod tests {
     fn test_ft_escrow_account_id() {
         let context = get_context(accounts(1));
         testing_env!(context.build());
-        let contract = Ft::new(MAX_SUPPLY.into(), accounts(1).into(), get_metadata());
+        let contract = Ft::new(
+            MAX_SUPPLY.into(),
+            accounts(1).into(),
+            accounts(4),
+            get_metadata(),
+        );
 
         assert_eq!(contract.ft_escrow_account_id(), accounts(1));
+        assert_eq!(contract.ft_treasury_account_id(), accounts(4));
     }
 
     #[test]
     fn test_claim() {
         let mut context = get_context(accounts(1));
-        testing_env!(context.signer_account_id(accounts(2).into()).build());
+        testing_env!(context.predecessor_account_id(accounts(2).into()).build());
 
-        let mut contract = Ft::new(MAX_SUPPLY.into(), accounts(1).into(), get_metadata());
+        let mut contract = Ft::new(
+            MAX_SUPPLY.into(),
+            accounts(1).into(),
+            accounts(4),
+            get_metadata(),
+        );
 
         // Account 2 Claim
         contract.claim();
@@ -203,14 +301,14 @@ mod tests {
             near_sdk::VMConfig::test(),
             near_sdk::RuntimeFeesConfig::test(),
             Default::default(),
-            vec![PromiseResult::Successful("100".to_string().into_bytes())],
+            vec![PromiseResult::Successful(get_share(100, 1000))],
         );
 
-        contract.on_claim_callback();
+        contract.on_claim_callback(accounts(2));
         assert_eq!(contract.ft_balance_of(accounts(2)).0, 100000000000000);
 
         // Account 3 Claim
-        testing_env!(context.signer_account_id(accounts(3).into()).build());
+        testing_env!(context.predecessor_account_id(accounts(3).into()).build());
         contract.claim();
 
         testing_env!(
@@ -218,10 +316,10 @@ mod tests {
             near_sdk::VMConfig::test(),
             near_sdk::RuntimeFeesConfig::test(),
             Default::default(),
-            vec![PromiseResult::Successful("200".to_string().into_bytes())],
+            vec![PromiseResult::Successful(get_share(200, 1000))],
         );
 
-        contract.on_claim_callback();
+        contract.on_claim_callback(accounts(3));
         assert_eq!(contract.ft_balance_of(accounts(3)).0, 200000000000000);
 
         assert_eq!(contract.ft_max_supply().0, MAX_SUPPLY);
@@ -232,9 +330,14 @@ mod tests {
     #[should_panic(expected = "ERR_MAX_SUPPLY_EXCEEDED")]
     fn test_claim_exceed_max_supply() {
         let mut context = get_context(accounts(1));
-        testing_env!(context.signer_account_id(accounts(2).into()).build());
+        testing_env!(context.predecessor_account_id(accounts(2).into()).build());
 
-        let mut contract = Ft::new(MAX_SUPPLY.into(), accounts(1).into(), get_metadata());
+        let mut contract = Ft::new(
+            MAX_SUPPLY.into(),
+            accounts(1).into(),
+            accounts(4),
+            get_metadata(),
+        );
 
         // Account 2 Claim
         contract.claim();
@@ -244,19 +347,24 @@ mod tests {
             near_sdk::VMConfig::test(),
             near_sdk::RuntimeFeesConfig::test(),
             Default::default(),
-            vec![PromiseResult::Successful("1100".to_string().into_bytes())],
+            vec![PromiseResult::Successful(get_share(1100, 1000))],
         );
 
-        contract.on_claim_callback();
+        contract.on_claim_callback(accounts(2));
     }
 
     #[test]
     #[should_panic(expected = "ERR_NO_TOKENS_TO_CLAIM")]
     fn test_claim_not_allowed() {
         let mut context = get_context(accounts(1));
-        testing_env!(context.signer_account_id(accounts(2).into()).build());
+        testing_env!(context.predecessor_account_id(accounts(2).into()).build());
 
-        let mut contract = Ft::new(MAX_SUPPLY.into(), accounts(1).into(), get_metadata());
+        let mut contract = Ft::new(
+            MAX_SUPPLY.into(),
+            accounts(1).into(),
+            accounts(4),
+            get_metadata(),
+        );
 
         // Account 2 Claim
         contract.claim();
@@ -266,19 +374,24 @@ mod tests {
             near_sdk::VMConfig::test(),
             near_sdk::RuntimeFeesConfig::test(),
             Default::default(),
-            vec![PromiseResult::Successful("0".to_string().into_bytes())],
+            vec![PromiseResult::Successful(get_share(0, 1000))],
         );
 
-        contract.on_claim_callback();
+        contract.on_claim_callback(accounts(2));
     }
 
     #[test]
     #[should_panic(expected = "ERR_ALREADY_CLAIMED_TOKENS")]
     fn test_claim_twice() {
         let mut context = get_context(accounts(1));
-        testing_env!(context.signer_account_id(accounts(2).into()).build());
+        testing_env!(context.predecessor_account_id(accounts(2).into()).build());
 
-        let mut contract = Ft::new(MAX_SUPPLY.into(), accounts(1).into(), get_metadata());
+        let mut contract = Ft::new(
+            MAX_SUPPLY.into(),
+            accounts(1).into(),
+            accounts(4),
+            get_metadata(),
+        );
 
         // Account 2 Claim
         contract.claim();
@@ -288,10 +401,10 @@ mod tests {
             near_sdk::VMConfig::test(),
             near_sdk::RuntimeFeesConfig::test(),
             Default::default(),
-            vec![PromiseResult::Successful("100".to_string().into_bytes())],
+            vec![PromiseResult::Successful(get_share(100, 1000))],
         );
 
-        contract.on_claim_callback();
+        contract.on_claim_callback(accounts(2));
 
         // Account 2 Claim Again
         contract.claim();
@@ -301,9 +414,128 @@ mod tests {
             near_sdk::VMConfig::test(),
             near_sdk::RuntimeFeesConfig::test(),
             Default::default(),
-            vec![PromiseResult::Successful("100".to_string().into_bytes())],
+            vec![PromiseResult::Successful(get_share(100, 1000))],
         );
 
-        contract.on_claim_callback();
+        contract.on_claim_callback(accounts(2));
+    }
+
+    #[test]
+    fn test_claim_below_one_per_mille() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+
+        let mut contract = Ft::new(MAX_SUPPLY.into(), accounts(1), accounts(4), get_metadata());
+
+        contract.claim();
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(get_share(1, 10_000))],
+        );
+
+        contract.on_claim_callback(accounts(2));
+        assert_eq!(contract.ft_balance_of(accounts(2)).0, MAX_SUPPLY / 10_000);
+    }
+
+    #[test]
+    fn test_claim_dust() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+
+        let mut contract = Ft::new(MAX_SUPPLY.into(), accounts(1), accounts(4), get_metadata());
+
+        contract.claim();
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(get_share(1, 3))],
+        );
+
+        contract.on_claim_callback(accounts(2));
+        assert_eq!(contract.ft_balance_of(accounts(2)).0, 333333333333333);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.claim();
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(get_share(2, 3))],
+        );
+
+        contract.on_claim_callback(accounts(3));
+        assert_eq!(contract.ft_balance_of(accounts(3)).0, 666666666666666);
+
+        // The treasury gets the rounding dust
+        assert_eq!(contract.ft_balance_of(accounts(4)).0, 1);
+        assert_eq!(contract.ft_total_supply().0, MAX_SUPPLY);
+    }
+
+    #[test]
+    fn test_claim_dust_before_all_claims() {
+        let mut context = get_context(accounts(1));
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+
+        let mut contract = Ft::new(MAX_SUPPLY.into(), accounts(1), accounts(4), get_metadata());
+
+        contract.claim();
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(get_share(1, 7))],
+        );
+
+        contract.on_claim_callback(accounts(2));
+        assert_eq!(contract.ft_balance_of(accounts(2)).0, 142857142857142);
+        assert_eq!(contract.ft_balance_of(accounts(4)).0, 0);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.claim();
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(get_share(1, 7))],
+        );
+
+        contract.on_claim_callback(accounts(3));
+        assert_eq!(contract.ft_balance_of(accounts(3)).0, 142857142857142);
+
+        // The dust is minted although the other shares are not claimed
+        assert_eq!(contract.ft_balance_of(accounts(4)).0, 1);
+        assert_eq!(contract.ft_total_supply().0, MAX_SUPPLY * 2 / 7);
+
+        // The treasury can still claim its own share
+        testing_env!(context.predecessor_account_id(accounts(4)).build());
+        contract.claim();
+
+        testing_env!(
+            context.build(),
+            near_sdk::VMConfig::test(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(get_share(5, 7))],
+        );
+
+        contract.on_claim_callback(accounts(4));
+        assert_eq!(
+            contract.ft_balance_of(accounts(4)).0,
+            MAX_SUPPLY - 142857142857142 * 2
+        );
+        assert_eq!(contract.ft_total_supply().0, MAX_SUPPLY);
     }
 }
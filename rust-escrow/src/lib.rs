@@ -1,7 +1,7 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::collections::UnorderedSet;
 use near_sdk::json_types::{Base64VecU8, U128};
-use near_sdk::serde_json::json;
+use near_sdk::serde_json::{json, Value};
 use near_sdk::{assert_self, env, near_bindgen, AccountId, Gas, Promise};
 
 const CONDITIONAL_ESCROW_CODE: &[u8] = include_bytes!("./conditional_escrow.wasm");
@@ -12,10 +12,22 @@ const CREATE_CALL_GAS: Gas = Gas(75_000_000_000_000);
 /// Gas allocated on the callback.
 const ON_CREATE_CALL_GAS: Gas = Gas(10_000_000_000_000);
 
+/// Platform fee charged by the Conditional Escrow contracts on successful delegation.
+const DEFAULT_FEE_BASIS_POINTS: u16 = 150; // 1.5%
+const MAX_FEE_BASIS_POINTS: u16 = 10_000; // 100%
+
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct EscrowFactory {
     conditional_escrow_contracts: UnorderedSet<AccountId>,
+    fee_basis_points: u16,
+    fee_account_id: AccountId,
+}
+
+/// State of the factories deployed before the platform fee, read by migrate
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct EscrowFactoryV1 {
+    conditional_escrow_contracts: UnorderedSet<AccountId>,
 }
 
 impl Default for EscrowFactory {
@@ -27,16 +39,50 @@ impl Default for EscrowFactory {
 #[near_bindgen]
 impl EscrowFactory {
     #[init]
-    pub fn new() -> Self {
+    pub fn new(fee_basis_points: Option<u16>, fee_account_id: Option<AccountId>) -> Self {
         if env::state_exists() {
             env::panic_str("ERR_ALREADY_INITIALIZED");
         }
-        
+
+        if fee_basis_points.unwrap_or(0) > MAX_FEE_BASIS_POINTS {
+            env::panic_str("ERR_INVALID_FEE_BASIS_POINTS");
+        }
+
         Self {
             conditional_escrow_contracts: UnorderedSet::new(b"d".to_vec()),
+            fee_basis_points: fee_basis_points.unwrap_or(DEFAULT_FEE_BASIS_POINTS),
+            fee_account_id: fee_account_id.unwrap_or_else(env::current_account_id),
+        }
+    }
+
+    /// Upgrades the state of a factory deployed before the platform fee, keeping its contracts
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate(fee_basis_points: Option<u16>, fee_account_id: Option<AccountId>) -> Self {
+        let state: EscrowFactoryV1 = match env::state_read() {
+            Some(state) => state,
+            None => env::panic_str("ERR_NOT_INITIALIZED"),
+        };
+
+        if fee_basis_points.unwrap_or(0) > MAX_FEE_BASIS_POINTS {
+            env::panic_str("ERR_INVALID_FEE_BASIS_POINTS");
+        }
+
+        Self {
+            conditional_escrow_contracts: state.conditional_escrow_contracts,
+            fee_basis_points: fee_basis_points.unwrap_or(DEFAULT_FEE_BASIS_POINTS),
+            fee_account_id: fee_account_id.unwrap_or_else(env::current_account_id),
         }
     }
 
+    pub fn get_fee_basis_points(&self) -> u16 {
+        self.fee_basis_points
+    }
+
+    pub fn get_fee_account_id(&self) -> AccountId {
+        self.fee_account_id.clone()
+    }
+
     pub fn get_conditional_escrow_contracts_list(&self) -> Vec<AccountId> {
         self.conditional_escrow_contracts.to_vec()
     }
@@ -68,7 +114,7 @@ impl EscrowFactory {
             .transfer(env::attached_deposit())
             .function_call(
                 "new".to_string(),
-                args.into(),
+                self.get_conditional_escrow_args(args),
                 0,
                 env::prepaid_gas() - CREATE_CALL_GAS - ON_CREATE_CALL_GAS,
             );
@@ -86,6 +132,27 @@ impl EscrowFactory {
         promise.then(callback)
     }
 
+    /// Sets the platform fee, the creator and the storage fee charged to depositors on the config of
+    /// the Conditional Escrow init args, overriding the ones sent by the creator.
+    fn get_conditional_escrow_args(&self, args: Base64VecU8) -> Vec<u8> {
+        let mut args: Value = match near_sdk::serde_json::from_slice(&Vec::<u8>::from(args)) {
+            Ok(Value::Object(args)) => Value::Object(args),
+            _ => env::panic_str("ERR_INVALID_CONDITIONAL_ESCROW_ARGS"),
+        };
+
+        let config = match args.get("config") {
+            None | Some(Value::Null) | Some(Value::Object(_)) => &mut args["config"],
+            _ => env::panic_str("ERR_INVALID_CONDITIONAL_ESCROW_ARGS"),
+        };
+
+        config["fee_basis_points"] = json!(self.fee_basis_points);
+        config["fee_account_id"] = json!(self.fee_account_id);
+        config["creator_account_id"] = json!(env::predecessor_account_id());
+        config["is_storage_fee_charged"] = json!(true);
+
+        args.to_string().into_bytes()
+    }
+
     pub fn on_create_conditional_escrow(
         &mut self,
         account_id: AccountId,
@@ -109,7 +176,7 @@ impl EscrowFactory {
 mod tests {
     use super::*;
     use chrono::Utc;
-    use near_sdk::test_utils::test_env::alice;
+    use near_sdk::test_utils::test_env::{alice, bob};
     use near_sdk::test_utils::VMContextBuilder;
     use near_sdk::PublicKey;
     use near_sdk::{testing_env, PromiseResult};
@@ -124,7 +191,7 @@ mod tests {
             .signer_account_pk(pk)
             .current_account_id(alice())
             .build());
-        let factory = EscrowFactory::new();
+        let factory = EscrowFactory::new(None, None);
         (context, factory)
     }
 
@@ -192,4 +259,65 @@ mod tests {
             alice(),
         );
     }
+
+    #[test]
+    fn test_migrate() {
+        let mut context = VMContextBuilder::new();
+        testing_env!(context
+            .current_account_id(alice())
+            .predecessor_account_id(alice())
+            .build());
+
+        let mut conditional_escrow_contracts = UnorderedSet::new(b"d".to_vec());
+        conditional_escrow_contracts.insert(&bob());
+        env::state_write(&EscrowFactoryV1 {
+            conditional_escrow_contracts,
+        });
+
+        let factory = EscrowFactory::migrate(Some(100), Some(bob()));
+
+        assert_eq!(factory.get_conditional_escrow_contracts_list(), vec![bob()]);
+        assert_eq!(factory.get_fee_basis_points(), 100);
+        assert_eq!(factory.get_fee_account_id(), bob());
+    }
+
+    #[test]
+    fn test_get_fee() {
+        let (_context, factory) = setup_contract();
+
+        assert_eq!(factory.get_fee_basis_points(), DEFAULT_FEE_BASIS_POINTS);
+        assert_eq!(factory.get_fee_account_id(), alice());
+    }
+
+    #[test]
+    fn test_get_conditional_escrow_args() {
+        let (_context, factory) = setup_contract();
+
+        let args = json!({ "expires_at": 100, "funding_amount_limit": "1000000000", "dao_factory_account_id": "daofactory.testnet", "ft_factory_account_id": "ftfactory.testnet", "metadata_url": "metadata_url.json", "config": { "fee_basis_points": 0, "funding_token_id": "usdc.testnet" } })
+            .to_string()
+            .into_bytes().to_vec().into();
+
+        assert_eq!(
+            near_sdk::serde_json::from_slice::<Value>(&factory.get_conditional_escrow_args(args))
+                .unwrap(),
+            json!({ "expires_at": 100, "funding_amount_limit": "1000000000", "dao_factory_account_id": "daofactory.testnet", "ft_factory_account_id": "ftfactory.testnet", "metadata_url": "metadata_url.json", "config": { "fee_basis_points": DEFAULT_FEE_BASIS_POINTS, "fee_account_id": alice(), "creator_account_id": bob(), "is_storage_fee_charged": true, "funding_token_id": "usdc.testnet" } }),
+            "Should set the factory fee, the creator and the storage fee"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_CONDITIONAL_ESCROW_ARGS")]
+    fn test_get_conditional_escrow_args_invalid() {
+        let (_context, factory) = setup_contract();
+
+        factory.get_conditional_escrow_args("[]".to_string().into_bytes().into());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_CONDITIONAL_ESCROW_ARGS")]
+    fn test_get_conditional_escrow_args_invalid_config() {
+        let (_context, factory) = setup_contract();
+
+        factory.get_conditional_escrow_args("{\"config\": []}".to_string().into_bytes().into());
+    }
 }